@@ -4,6 +4,7 @@ use std::fmt;
 use constants::*;
 use types::*;
 use env::*;
+use error::*;
 
 use types::InputValue::*;
 use types::Action::*;
@@ -22,7 +23,9 @@ pub struct AST {
     blocks: Vec<SectionType>,
     pub elif: Option<SectionType>,
     pub sec_loop: bool,
-    pub no_jump: bool
+    pub no_jump: bool,
+    // set by try!, failures of the command after it are ignored
+    pub unchecked: bool
 }
 
 impl fmt::Debug for AST {
@@ -55,7 +58,8 @@ impl AST {
             blocks: vec![],
             elif: None,
             sec_loop: false,
-            no_jump: false
+            no_jump: false,
+            unchecked: false
         }
     }
 
@@ -68,12 +72,13 @@ impl AST {
         self.elif = None;
         self.sec_loop = false;
         self.no_jump = false;
+        self.unchecked = false;
     }
 
     pub fn into_runner(&mut self) -> ASTRunner {
         let sections = self.sections.clone();
         self.clear();
-        ASTRunner::new(sections)
+        ASTRunner::new(&String::new(), sections)
     }
     
     pub fn in_block(&self) -> bool {
@@ -84,6 +89,11 @@ impl AST {
         self.handlers.insert(word.to_string(), callback);
     }
 
+    // for highlighting them in the line editor
+    pub fn handler_names(&self) -> Vec<String> {
+        self.handlers.keys().map(|k| k.clone()).collect()
+    }
+
     pub fn add_endline(&mut self, action:Action) {
         self.endline.push_back(action);
     }
@@ -116,24 +126,57 @@ impl AST {
         self.position
     }
 
-    pub fn add_line(&mut self, line:&mut InputValue) -> Result<(), String> {
-        let mut aclist = try!(self.process(line, true));
+    // Positions are kept per line rather than per word: every section
+    // the line put actions in gets a Mark for it in front of them
+    pub fn add_line(&mut self, line:&mut InputValue, pos:SourcePos) -> WashResult<()> {
+        // where each section ended before this line
+        let before:HashMap<SectionType, usize> = self.sections.iter()
+            .map(|(key, section)| (*key, section.len())).collect();
+        let processed = self.process(line, true);
+        self.unchecked = false;
+        let mut aclist = try!(processed);
         aclist.append(&mut self.endline);
         if !self.sections.contains_key(&self.position) {
             self.sections.insert(self.position, LinkedList::new());
         }
         match self.sections.get_mut(&self.position) {
-            None => Err(format!("Position not found in section table")),
-            Some(mut section) => {
-                section.append(&mut aclist);
-                Ok(())
+            None => return Err(WashError::parse(format!("Position not found in section table"))),
+            Some(mut section) => section.append(&mut aclist)
+        }
+        // handlers like func! and && put actions in other sections,
+        // which have to say where they came from too
+        for (key, section) in self.sections.iter_mut() {
+            let start = before.get(key).map_or(0, |n| *n);
+            if section.len() > start {
+                let mut added = section.split_off(start);
+                section.push_back(Mark(pos.clone()));
+                section.append(&mut added);
             }
         }
+        Ok(())
+    }
+
+    // checks the output of a command run on a line
+    pub fn describe_call(&self) -> Action {
+        if self.unchecked {
+            Call(format!("ignore_process_output"))
+        } else {
+            Call(format!("describe_process_output"))
+        }
+    }
+
+    // checks the output of a command followed by &;
+    pub fn strict_call(&self) -> Action {
+        if self.unchecked {
+            Call(format!("ignore_process_output"))
+        } else {
+            Call(format!("strict_process_output"))
+        }
     }
 
-    pub fn end_block(&mut self) -> Result<(), String> {
+    pub fn end_block(&mut self) -> WashResult<()> {
         match self.blocks.pop() {
-            None => Err(format!("No block to end")),
+            None => Err(WashError::parse(format!("No block to end"))),
             Some(SectionType::Number(n)) => {
                 if self.sec_loop {
                     match self.position {
@@ -157,7 +200,7 @@ impl AST {
         }
     }
 
-    pub fn process(&mut self, line:&mut InputValue, run:bool) -> Result<LinkedList<Action>, String> {
+    pub fn process(&mut self, line:&mut InputValue, run:bool) -> WashResult<LinkedList<Action>> {
         match line {
             &mut Split(_) => Ok(LinkedList::new()),
             &mut Short(ref s) if self.handlers.contains_key(s) => {
@@ -184,7 +227,7 @@ impl AST {
                         out.push_back(Set(WashArgs::Flat(s.clone())));
                         if run {
                             out.push_back(Call(format!("run")));
-                            out.push_back(Call(format!("describe_process_output")));
+                            out.push_back(self.describe_call());
                         }
                     },
                     Some(caps) => {
@@ -249,13 +292,13 @@ impl AST {
                     out.push_back(Pull);
                     if run {
                         out.push_back(Call(format!("run")));
-                        out.push_back(Call(format!("describe_process_output")));
+                        out.push_back(self.describe_call());
                     }
                 } else if count > 1 {
                     out.push_back(Join(count));
                     if run {
                         out.push_back(Call(format!("run")));
-                        out.push_back(Call(format!("describe_process_output")));
+                        out.push_back(self.describe_call());
                     }
                 }
                 Ok(out)
@@ -280,13 +323,13 @@ impl AST {
         }
     }
 
-    pub fn optimize(&mut self) -> Result<(), String> {
+    pub fn optimize(&mut self) -> WashResult<()> {
         try!(self.opcombine());
         try!(self.jumpreduce());
         Ok(())
     }
 
-    pub fn jumpreduce(&mut self) -> Result<bool, String> {
+    pub fn jumpreduce(&mut self) -> WashResult<bool> {
         // hashmap of sectiontype to (len, jumps_to, jumped_to_from)
         let mut jumps = HashMap::<SectionType, (usize, HashSet<SectionType>, HashSet<SectionType>)>::new();
         let mut visited = HashSet::new();
@@ -310,7 +353,7 @@ impl AST {
             }
             visited.insert(position);
             let section = match self.sections.get(&position) {
-                None => return Err(format!("Section not found: {:?}", position)),
+                None => return Err(WashError::compile(format!("Section not found: {:?}", position))),
                 Some(sec) => sec
             };
             if jumps.contains_key(&position) {
@@ -395,12 +438,12 @@ impl AST {
                         t = *(moved.get(&t).unwrap());
                     }
                     let destsec = match self.sections.get_mut(&t) {
-                        None => return Err(format!("Pass 0: Destination {:?} not found", t)),
+                        None => return Err(WashError::compile(format!("Pass 0: Destination {:?} not found", t))),
                         Some(sec) => sec
                     };
                     loop {
                         match destsec.pop_back() {
-                            None => return Err(format!("Pass 0: No jump found in section")),
+                            None => return Err(WashError::compile(format!("Pass 0: No jump found in section"))),
                             Some(Jump(ref n)) if *n == num => {
                                 // we've found the jump to our section
                                 break;
@@ -433,17 +476,17 @@ impl AST {
                     continue;
                 }
                 let mut orig = match self.sections.remove(&position) {
-                    None => return Err(format!("Pass 1: Original {:?} not found", position)),
+                    None => return Err(WashError::compile(format!("Pass 1: Original {:?} not found", position))),
                     Some(sec) => sec
                 };
                 moved.insert(position, dest);
                 let destsec = match self.sections.get_mut(&dest) {
-                    None => return Err(format!("Pass 1: Destination {:?} not found", dest)),
+                    None => return Err(WashError::compile(format!("Pass 1: Destination {:?} not found", dest))),
                     Some(sec) => sec
                 };
                 loop {
                     match destsec.pop_back() {
-                        None => return Err(format!("Pass 1: No jump found in section")),
+                        None => return Err(WashError::compile(format!("Pass 1: No jump found in section"))),
                         Some(Jump(ref n)) if *n == num => {
                             // we've found the jump to our section
                             destsec.append(&mut orig);
@@ -464,7 +507,7 @@ impl AST {
         Ok(changes)
     }
 
-    pub fn opcombine(&mut self) -> Result<bool, String> {
+    pub fn opcombine(&mut self) -> WashResult<bool> {
         let mut visited = HashSet::new();
         let mut to_visit = vec![];
         let mut position = SectionType::Run;
@@ -480,7 +523,7 @@ impl AST {
             }
             visited.insert(position);
             section = match self.sections.remove(&position) {
-                None => return Err(format!("Section not found: {:?}", position)),
+                None => return Err(WashError::compile(format!("Section not found: {:?}", position))),
                 Some(sec) => sec
             };
             out = LinkedList::new();
@@ -590,7 +633,7 @@ impl AST {
                                     let name; let path;
                                     match VAR_PATH_REGEX.captures(var.as_slice()) {
                                         None => match VAR_REGEX.captures(var.as_slice()) {
-                                            None => return Err(format!("Load would have failed with {}", var)),
+                                            None => return Err(WashError::compile(format!("Load would have failed with {}", var))),
                                             Some(caps) => {
                                                 name = caps.at(1).unwrap().to_string();
                                                 path = String::new();
@@ -0,0 +1,244 @@
+// Completion from bash: a helper bash loads the bash-completion scripts,
+// runs the completion for a command with COMP_WORDS and COMP_CWORD set
+// from the line, and prints COMPREPLY back
+use std::old_io::process::{Command, Process};
+use std::old_io::process::StdioContainer::*;
+use std::old_io::IoErrorKind::EndOfFile;
+use std::os::unix::prelude::*;
+use std::collections::*;
+
+use constants::*;
+use signal::*;
+use ioctl::*;
+use error::*;
+
+// run after the words are set, exits with 1 if the command has no completion
+const BASH_COMPLETE:&'static str = r#"
+cmd=${COMP_WORDS[0]}
+cur=${COMP_WORDS[COMP_CWORD]}
+prev=${COMP_WORDS[COMP_CWORD-1]}
+COMP_LINE="${COMP_WORDS[*]}"
+COMP_POINT=${#COMP_LINE}
+COMP_TYPE=9
+COMP_KEY=9
+spec=$(complete -p "$cmd" 2>/dev/null)
+if [ -z "$spec" ]; then
+    # bash-completion loads most completions the first time they're used
+    if declare -F _completion_loader >/dev/null; then
+        _completion_loader "$cmd"
+    elif declare -F __load_completion >/dev/null; then
+        __load_completion "$cmd"
+    fi
+    spec=$(complete -p "$cmd" 2>/dev/null)
+fi
+[ -z "$spec" ] && exit 1
+if [[ $spec =~ -F\ ([^ ]+) ]]; then
+    "${BASH_REMATCH[1]}" "$cmd" "$cur" "$prev"
+else
+    args=${spec#complete }
+    eval "COMPREPLY=(\$(compgen ${args% *} -- \"\$cur\"))"
+fi
+printf '%s\n' "${COMPREPLY[@]}"
+"#;
+
+pub struct BashCompleter {
+    // commands bash has no completion for, so it isn't asked again
+    missing: HashSet<String>,
+    // replies by command, then by the words they were for, kept
+    // until the next line runs
+    cache: HashMap<String, HashMap<String, Vec<String>>>
+}
+
+fn quote(s:&String) -> String {
+    format!("'{}'", s.replace("'", "'\\''"))
+}
+
+impl BashCompleter {
+    pub fn new() -> BashCompleter {
+        BashCompleter {
+            missing: HashSet::new(),
+            cache: HashMap::new()
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.missing.clear();
+        self.cache.clear();
+    }
+
+    // a line that ran could have changed the directory, or anything
+    // else the replies depend on
+    pub fn line_ran(&mut self) {
+        self.cache.clear();
+    }
+
+    // what bash would complete the last of words with. Callers narrow
+    // the replies down
+    pub fn complete(&mut self, script:&str, words:&Vec<String>) -> Vec<String> {
+        if words.len() < 2 {
+            return vec![];
+        }
+        let command = words[0].clone();
+        if self.missing.contains(&command) {
+            return vec![];
+        }
+        // completions look at the word being completed, like only
+        // offering --options after a -
+        let key = format!("{:?}", words);
+        match self.cache.get(&command) {
+            None => {},
+            Some(replies) => match replies.get(&key) {
+                None => {},
+                Some(r) => return r.clone()
+            }
+        }
+        match run_bash(script, words) {
+            // try again next time
+            Err(_) => vec![],
+            Ok(None) => {
+                self.missing.insert(command);
+                vec![]
+            },
+            Ok(Some(replies)) => {
+                if !self.cache.contains_key(&command) {
+                    self.cache.insert(command.clone(), HashMap::new());
+                }
+                self.cache.get_mut(&command).unwrap().insert(key, replies.clone());
+                replies
+            }
+        }
+    }
+}
+
+// None if bash doesn't know how to complete the command
+fn run_bash(script:&str, words:&Vec<String>) -> WashResult<Option<Vec<String>>> {
+    if !can_requeue() {
+        // job exits taken while waiting couldn't be passed on
+        return Err(WashError::runtime(format!("Bash completion isn't supported here")));
+    }
+    let mut set = tryf!(empty_sigset(), "Couldn't make signal set: {err}");
+    tryf!(sigset_add(&mut set, SIGCHLD), "Couldn't make signal set: {err}");
+    // so the helper finishing isn't taken for a job
+    let old_set = tryf!(signal_proc_mask(SIG_BLOCK, &set), "Couldn't block SIGCHLD: {err}");
+    // the mask as it is now, kept while waiting for output
+    let mask = tryf!(signal_proc_mask(SIG_BLOCK, &set), "Couldn't block SIGCHLD: {err}");
+    let mut others = vec![];
+    let out = run_bash_blocked(script, words, &set, &mask, &mut others);
+    tryf!(signal_proc_mask(SIG_SETMASK, &old_set), "Couldn't unblock SIGCHLD: {err}");
+    // jobs that finished meanwhile, for the job table to see
+    for info in others.iter() {
+        tryf!(signal_requeue(info), "Couldn't pass on SIGCHLD: {err}");
+    }
+    return out;
+}
+
+fn run_bash_blocked(script:&str, words:&Vec<String>, set:&SigSet, mask:&SigSet,
+                    others:&mut Vec<SigInfo>) -> WashResult<Option<Vec<String>>> {
+    let mut process = Command::new("bash");
+    process.args(&["--norc", "--noprofile", "-s"]);
+    process.stdin(CreatePipe(true, false));
+    process.stdout(CreatePipe(false, true));
+    process.stderr(Ignored);
+    let mut child = match process.spawn() {
+        Err(e) => return Err(WashError::spawn(format!("Couldn't start bash: {}", e))),
+        Ok(c) => c
+    };
+    let deadline = get_time_ms() + BASH_TIMEOUT as u64;
+    let mut input = format!("source {} >/dev/null 2>&1\nCOMP_WORDS=(", quote(&script.to_string()));
+    for word in words.iter() {
+        input.push_str(quote(word).as_slice());
+        input.push(' ');
+    }
+    input.push_str(format!(")\nCOMP_CWORD={}\n", words.len() - 1).as_slice());
+    input.push_str(BASH_COMPLETE);
+    match child.stdin.take() {
+        None => return Err(WashError::runtime(format!("Bash had no stdin"))),
+        // dropped after, so bash sees the end of the script
+        Some(mut stdin) => tryf!(stdin.write_str(input.as_slice()),
+                                 "Couldn't write to bash: {err}")
+    }
+    // all of the output comes first, so bash never waits on a full pipe
+    let result = match read_output(&mut child, deadline, mask) {
+        Err(e) => Err(e),
+        Ok(output) => match wait_bash(&child, Some(deadline), set, others) {
+            Err(e) => Err(e),
+            Ok(status) => Ok((output, status))
+        }
+    };
+    let (output, status) = match result {
+        Err(e) => {
+            // don't leave it running, or unwaited for
+            let _ = child.signal_kill();
+            let _ = wait_bash(&child, None, set, others);
+            return Err(e);
+        },
+        Ok(v) => v
+    };
+    if status == 1 {
+        return Ok(None);
+    }
+    let mut out = vec![];
+    for line in String::from_utf8_lossy(output.as_slice()).lines() {
+        if !line.is_empty() && !out.contains(&line.to_string()) {
+            out.push(line.to_string());
+        }
+    }
+    return Ok(Some(out));
+}
+
+// everything bash prints, until it closes stdout
+fn read_output(child:&mut Process, deadline:u64, mask:&SigSet) -> WashResult<Vec<u8>> {
+    let stdout = match child.stdout.as_mut() {
+        None => return Err(WashError::runtime(format!("Bash had no stdout"))),
+        Some(st) => st
+    };
+    let fd = stdout.as_raw_fd();
+    let mut out = vec![];
+    let mut buf = [0u8; 4096];
+    loop {
+        let now = get_time_ms();
+        if now >= deadline {
+            return Err(WashError::runtime(format!("Bash completion timed out")));
+        }
+        match select(&vec![fd], &vec![], &vec![], Some((deadline - now) as usize), mask) {
+            // interrupted or nothing yet, the deadline is checked again
+            Err(_) => continue,
+            Ok(ref ready) if ready.is_empty() => continue,
+            Ok(_) => {}
+        }
+        match stdout.read(&mut buf) {
+            Ok(n) => out.push_all(&buf[..n]),
+            Err(ref e) if e.kind == EndOfFile => return Ok(out),
+            Err(e) => return Err(WashError::runtime(format!("Couldn't read from bash: {}", e)))
+        }
+    }
+}
+
+// bash's exit status, or -1 if it was killed. SIGCHLDs from other
+// children are kept in others
+fn wait_bash(child:&Process, deadline:Option<u64>, set:&SigSet,
+             others:&mut Vec<SigInfo>) -> WashResult<i32> {
+    loop {
+        let timeout = match deadline {
+            None => None,
+            Some(d) => {
+                let now = get_time_ms();
+                if now >= d {
+                    return Err(WashError::runtime(format!("Bash completion timed out")));
+                }
+                Some((d - now) as usize)
+            }
+        };
+        let info = match signal_wait_set(set, timeout) {
+            // timed out or interrupted, the deadline is checked again
+            Err(_) => continue,
+            Ok(i) => i
+        };
+        match info.determine_sigfields() {
+            SigFields::SigChld(ref f) if f.pid == child.id() => {
+                return Ok(if info.code == CLD_EXITED {f.status} else {-1});
+            },
+            _ => others.push(info)
+        }
+    }
+}
@@ -1,6 +1,24 @@
 #![feature(old_io)]
+#![feature(libc)]
+extern crate libc;
+
 use std::old_io;
 
+// the notation bind uses
+#[allow(dead_code)]
+#[path = "../keys.rs"]
+mod keys;
+
+#[allow(dead_code)]
+#[path = "../termios.rs"]
+mod termios;
+
+#[path = "../termconst.rs"]
+mod termconst;
+
+use termios::*;
+use termconst::*;
+
 fn empty_escape(esc:&mut Iterator<Item=char>) -> String {
     let mut out = String::new();
     loop {
@@ -12,10 +30,40 @@ fn empty_escape(esc:&mut Iterator<Item=char>) -> String {
     return out;
 }
 
+// everything one key press sends comes in together, so one read is one key
+fn read_key() -> String {
+    let mut stdin = old_io::stdio::stdin_raw();
+    let mut buf = [0u8; 64];
+    let n = match stdin.read(&mut buf) {
+        Err(e) => panic!("Couldn't read key: {}", e),
+        Ok(n) => n
+    };
+    return String::from_utf8_lossy(&buf[..n]).into_owned();
+}
+
 pub fn main() {
-    let mut stdin = old_io::stdin();
+    let old_tios = match Termios::get() {
+        Err(e) => {
+            println!("key_helper needs a terminal: {}", e);
+            return;
+        },
+        Ok(t) => t
+    };
+    // keys are read as they're pressed, like the line editor does
+    let mut tios = old_tios.clone();
+    // and C-c, C-z, C-s and C-q come through as keys
+    tios.fdisable(0, IXON, ICANON|ECHO|ISIG, 0);
+    Termios::set(&tios).unwrap();
 
-    print!("Type a key: ");
-    let c = stdin.read_char().unwrap();
-    println!("In escaped form: {}", empty_escape(&mut c.escape_default()));
+    print!("Press a key: ");
+    old_io::stdio::flush();
+    let typed = read_key();
+    Termios::set(&old_tios).unwrap();
+
+    let mut escaped = String::new();
+    for c in typed.chars() {
+        escaped.push_str(empty_escape(&mut c.escape_default()).as_slice());
+    }
+    println!("\nIn escaped form: {}", escaped);
+    println!("For bind: {}", keys::describe_input(typed.as_slice()));
 }
@@ -0,0 +1,249 @@
+// Key bindings for the line editor: sequences of keys, written as in
+// keys.rs, bound to the names of editing actions
+use std::collections::*;
+
+use types::*;
+use types::WashArgs::*;
+use reader::*;
+use env::*;
+use keys::*;
+use error::*;
+
+use self::KeyLookup::*;
+
+pub type EditAction = fn(&mut LineReader, &WashEnv) -> bool;
+
+pub enum KeyLookup {
+    Bound(String),
+    // more keys are needed to know
+    Prefix,
+    Unbound
+}
+
+pub struct Keymap {
+    bindings: HashMap<String, String>
+}
+
+macro_rules! action {
+    ($name:ident, $reader:pat, $env:pat, $func:block) => {
+        fn $name($reader:&mut LineReader, $env:&WashEnv) -> bool
+            $func
+    }
+}
+
+action!(accept_line, reader, _, {
+    reader.accept_line()
+});
+
+action!(end_of_file, reader, _, {
+    reader.end_of_file()
+});
+
+action!(backward_delete_char, reader, _, {
+    reader.delete_char()
+});
+
+action!(delete_char, reader, _, {
+    reader.delete_next_char()
+});
+
+action!(beginning_of_line, reader, _, {
+    reader.beginning_of_line()
+});
+
+action!(end_of_line, reader, _, {
+    reader.end_of_line()
+});
+
+action!(backward_char, reader, _, {
+    reader.backward_char()
+});
+
+action!(forward_char, reader, _, {
+    reader.forward_char()
+});
+
+action!(kill_line, reader, _, {
+    reader.kill_line()
+});
+
+action!(backward_word, reader, _, {
+    reader.backward_word()
+});
+
+action!(forward_word, reader, _, {
+    reader.forward_word()
+});
+
+action!(backward_kill_word, reader, _, {
+    reader.backward_kill_word()
+});
+
+action!(kill_word, reader, _, {
+    reader.kill_word()
+});
+
+action!(unix_line_discard, reader, _, {
+    reader.unix_line_discard()
+});
+
+action!(yank, reader, _, {
+    reader.yank()
+});
+
+action!(yank_pop, reader, _, {
+    reader.yank_pop()
+});
+
+action!(undo, reader, _, {
+    reader.undo()
+});
+
+action!(redo, reader, _, {
+    reader.redo()
+});
+
+action!(history_prev, reader, _, {
+    reader.history_prev()
+});
+
+action!(history_next, reader, _, {
+    reader.history_next()
+});
+
+action!(history_search, reader, _, {
+    reader.start_search()
+});
+
+action!(complete, reader, env, {
+    reader.complete(env)
+});
+
+// everything keys can be bound to, by name
+pub fn edit_actions() -> HashMap<String, EditAction> {
+    let mut actions:HashMap<String, EditAction> = HashMap::new();
+    actions.insert("accept-line".to_string(), accept_line);
+    actions.insert("end-of-file".to_string(), end_of_file);
+    actions.insert("backward-delete-char".to_string(), backward_delete_char);
+    actions.insert("delete-char".to_string(), delete_char);
+    actions.insert("beginning-of-line".to_string(), beginning_of_line);
+    actions.insert("end-of-line".to_string(), end_of_line);
+    actions.insert("backward-char".to_string(), backward_char);
+    actions.insert("forward-char".to_string(), forward_char);
+    actions.insert("kill-line".to_string(), kill_line);
+    actions.insert("backward-word".to_string(), backward_word);
+    actions.insert("forward-word".to_string(), forward_word);
+    actions.insert("backward-kill-word".to_string(), backward_kill_word);
+    actions.insert("kill-word".to_string(), kill_word);
+    actions.insert("unix-line-discard".to_string(), unix_line_discard);
+    actions.insert("yank".to_string(), yank);
+    actions.insert("yank-pop".to_string(), yank_pop);
+    actions.insert("undo".to_string(), undo);
+    actions.insert("redo".to_string(), redo);
+    actions.insert("history-prev".to_string(), history_prev);
+    actions.insert("history-next".to_string(), history_next);
+    actions.insert("history-search".to_string(), history_search);
+    actions.insert("complete".to_string(), complete);
+    return actions;
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        let mut keymap = Keymap {
+            bindings: HashMap::new()
+        };
+        for &(keys, action) in [("RET", "accept-line"),
+                                ("C-d", "end-of-file"),
+                                ("DEL", "backward-delete-char"),
+                                ("C-h", "backward-delete-char"),
+                                ("Delete", "delete-char"),
+                                ("C-a", "beginning-of-line"),
+                                ("Home", "beginning-of-line"),
+                                ("C-e", "end-of-line"),
+                                ("End", "end-of-line"),
+                                ("C-b", "backward-char"),
+                                ("Left", "backward-char"),
+                                ("C-f", "forward-char"),
+                                ("Right", "forward-char"),
+                                ("C-k", "kill-line"),
+                                ("M-b", "backward-word"),
+                                ("M-f", "forward-word"),
+                                ("C-Left", "backward-word"),
+                                ("M-Left", "backward-word"),
+                                ("C-Right", "forward-word"),
+                                ("M-Right", "forward-word"),
+                                ("C-w", "backward-kill-word"),
+                                ("M-DEL", "backward-kill-word"),
+                                ("M-d", "kill-word"),
+                                ("C-u", "unix-line-discard"),
+                                ("C-y", "yank"),
+                                ("M-y", "yank-pop"),
+                                ("C-_", "undo"),
+                                ("M-_", "redo"),
+                                ("C-p", "history-prev"),
+                                ("Up", "history-prev"),
+                                ("C-n", "history-next"),
+                                ("Down", "history-next"),
+                                ("C-r", "history-search"),
+                                ("TAB", "complete")].iter() {
+            keymap.bindings.insert(keys.to_string(), action.to_string());
+        }
+        return keymap;
+    }
+
+    fn parse(keys:&str) -> WashResult<String> {
+        match parse_keys(keys) {
+            None => Err(WashError::runtime(format!("Not a key sequence: {}", keys))),
+            Some(k) => Ok(k.connect(" "))
+        }
+    }
+
+    pub fn bind(&mut self, keys:&str, action:&str) -> WashResult<()> {
+        let keys = try!(Keymap::parse(keys));
+        if !edit_actions().contains_key(action) {
+            return Err(WashError::runtime(format!("Unknown editing action: {}", action)));
+        }
+        self.bindings.insert(keys, action.to_string());
+        return Ok(());
+    }
+
+    pub fn unbind(&mut self, keys:&str) -> WashResult<()> {
+        let keys = try!(Keymap::parse(keys));
+        match self.bindings.remove(&keys) {
+            None => Err(WashError::runtime(format!("{} isn't bound", keys))),
+            Some(_) => Ok(())
+        }
+    }
+
+    // sequence is keys typed so far, in the form parse_keys gives
+    pub fn lookup(&self, sequence:&String) -> KeyLookup {
+        match self.bindings.get(sequence) {
+            Some(action) => return Bound(action.clone()),
+            None => {}
+        }
+        let start = format!("{} ", sequence);
+        if self.bindings.keys().any(|k| k.starts_with(start.as_slice())) {
+            return Prefix;
+        }
+        return Unbound;
+    }
+
+    pub fn get_args(&self, keys:&str) -> WashResult<WashArgs> {
+        let keys = try!(Keymap::parse(keys));
+        match self.bindings.get(&keys) {
+            None => Ok(Empty),
+            Some(action) => Ok(Flat(action.clone()))
+        }
+    }
+
+    pub fn all_args(&self) -> WashArgs {
+        let mut keys:Vec<&String> = self.bindings.keys().collect();
+        keys.sort();
+        let mut out = vec![];
+        for keys in keys.iter() {
+            out.push(Long(vec![Flat((*keys).clone()),
+                               Flat(self.bindings.get(*keys).unwrap().clone())]));
+        }
+        return Long(out);
+    }
+}
@@ -1,5 +1,6 @@
 use regex::Regex;
 
+use std::old_io::process::ProcessExit;
 use std::old_io::process::ProcessExit::*;
 use std::os::unix::prelude::*;
 use std::path::PathBuf;
@@ -17,18 +18,19 @@ use constants::*;
 use types::*;
 use env::*;
 use ioctl::*;
+use error::*;
 
 macro_rules! builtin {
     ($name:ident, $args:pat, $env:pat, $func:block) => {
-        pub fn $name($args:&WashArgs, $env:&mut WashEnv) -> Result<WashArgs, String>
+        pub fn $name($args:&WashArgs, $env:&mut WashEnv) -> WashResult<WashArgs>
             $func
     }
 }
 
 builtin!(source_func, args, env, {
     let name = match args {
-        &Empty => return Err("No arguments given".to_string()),
-        &Long(_) => return Err("Can only source flat names".to_string()),
+        &Empty => return Err(WashError::runtime("No arguments given".to_string())),
+        &Long(_) => return Err(WashError::runtime("Can only source flat names".to_string())),
         &Flat(ref v) => v.clone()
     };
     env.load_script(PathBuf::new(&name), &args.slice(1, -1))
@@ -38,7 +40,7 @@ builtin!(getall_func, args, env, {
     match args {
         &Empty => env.getall(),
         &Flat(ref p) => env.getallp(p),
-        _ => Err(format!("Path must be Flat or Empty"))
+        _ => Err(WashError::runtime(format!("Path must be Flat or Empty")))
     }
 });
 
@@ -58,7 +60,7 @@ builtin!(cd_func, args, _, {
         }
     };
     match env::set_current_dir(&old_path::Path::new(newp.as_os_str().to_str().unwrap())) {
-        Err(e) => return Err(format!("{}", e)),
+        Err(e) => return Err(WashError::runtime(format!("{}", e))),
         Ok(_) => return Ok(Empty)
     }
 });
@@ -74,7 +76,7 @@ builtin!(outs_func, args, env, {
 
 builtin!(equal_func, args, _, {
     if !args.is_long() || !args.len() == 2 {
-        Err(format!("Invalid arguments to equals?"))
+        Err(WashError::runtime(format!("Invalid arguments to equals?")))
     } else if args.get(0) == args.get(1) {
         Ok(Empty)
     } else {
@@ -84,7 +86,7 @@ builtin!(equal_func, args, _, {
 
 builtin!(re_equal_func, args, _, {
     if !args.is_long() || !args.len() == 2 {
-        Err(format!("Invalid arguments to equals?"))
+        Err(WashError::runtime(format!("Invalid arguments to equals?")))
     } else if args.get(1).is_flat() {
         let re = tryf!(Regex::new(args.get(1).flatten().as_slice()), "{err}");
         if re.is_match(args.get(0).flatten().as_slice()) {
@@ -93,7 +95,7 @@ builtin!(re_equal_func, args, _, {
             Ok(Flat(format!("not equal")))
         }
     } else {
-        Err(format!("Right-hand side must be flat (regex)"))
+        Err(WashError::runtime(format!("Right-hand side must be flat (regex)")))
     }
 });
 
@@ -112,7 +114,7 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
     let (mut stdin, mut stdout, mut stderr) = (None, None, None);
     let mut argc = match args {
         &Long(ref v) => v.clone(),
-        _ => return Err(format!("Not given Long"))
+        _ => return Err(WashError::runtime(format!("Not given Long")))
     };
     let mut envs = vec![];
     let mut name; let mut fname;
@@ -121,7 +123,7 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
         try!(env.func_stop());
         // fail if only file descriptors given
         if argc.is_empty() {
-            return Err("No command given".to_string());
+            return Err(WashError::runtime("No command given".to_string()));
         }
         // pop out arguments from the front until no more file descriptors remain
         name = argc.remove(0);
@@ -132,7 +134,7 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
         } else if FD_REGEX.is_match(fname.as_slice()) {
             let caps = FD_REGEX.captures(fname.as_slice()).unwrap();
             match from_str_radix::<Fd>(caps.at(2).unwrap(), 10) {
-                Err(e) => return Err(format!("{} could not be turned into usize: {}", caps.at(2).unwrap(), e)),
+                Err(e) => return Err(WashError::runtime(format!("{} could not be turned into usize: {}", caps.at(2).unwrap(), e))),
                 Ok(fd) => match caps.at(1).unwrap() {
                     path if path.is_empty() || path == "in" =>
                         // default to stdin
@@ -141,7 +143,7 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
                         stdout = Some(fd as Fd),
                     path if path == "err" =>
                         stderr = Some(fd as Fd),
-                    _ => return Err(format!("{} is not a valid standard output", caps.at(1).unwrap()))
+                    _ => return Err(WashError::runtime(format!("{} is not a valid standard output", caps.at(1).unwrap())))
                 }
             }
         } else if EQ_TEMP_REGEX.is_match(fname.as_slice()) {
@@ -149,16 +151,16 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
             let caps = EQ_TEMP_REGEX.captures(fname.as_slice()).unwrap();
             let path = caps.at(1).unwrap();
             if path != "env" {
-                return Err(format!("Can only set environment variables on commands"));
+                return Err(WashError::runtime(format!("Can only set environment variables on commands")));
             }
             let name = caps.at(2).unwrap();
             if argc.is_empty() {
-                return Err(format!("Incomplete temporary variable decleration"));
+                return Err(WashError::runtime(format!("Incomplete temporary variable decleration")));
             }
             let val = match argc.remove(0) {
                 Empty => None,
                 Flat(s) => Some(s),
-                _ => return Err(format!("Environment variables can only be flat"))
+                _ => return Err(WashError::runtime(format!("Environment variables can only be flat")))
             };
             envs.push((name.to_string(), val));
         } else {
@@ -172,22 +174,22 @@ fn job_args(args:&WashArgs, env:&mut WashEnv) -> Result<(Option<Fd>, Option<Fd>,
 builtin!(job_func, args, env, {
     let id;
     if args.is_empty() || args.len() < 1 {
-        return Err("No arguments given".to_string());
+        return Err(WashError::runtime("No arguments given".to_string()));
     } else if args.is_flat() {
         // easy case, no arguments to the function
         if env.hasf(&args.flatten()) {
-            return Err(format!("Cannot run functions as jobs"));
+            return Err(WashError::runtime(format!("Cannot run functions as jobs")));
         } else {
             id = try!(env.run_job(&args.flatten(), &vec![]));
         }
     } else if !args.get(0).is_flat() {
-        return Err("Can only run flat names".to_string());
+        return Err(WashError::runtime("Can only run flat names".to_string()));
     } else if !FD_REGEX.is_match(args.get(0).flatten().as_slice()) &&
         !EQ_TEMP_REGEX.is_match(args.get(0).flatten().as_slice()) {
             // easy case, just a command
             let args_slice = args.slice(1, -1);
             if env.hasf(&args.get(0).flatten()) {
-                return Err(format!("Cannot run functions as jobs"));
+                return Err(WashError::runtime(format!("Cannot run functions as jobs")));
             } else {
                 id = try!(env.run_job(&args.get(0).flatten(), &args_slice.flatten_vec()));
             }
@@ -195,7 +197,7 @@ builtin!(job_func, args, env, {
             // hard case, full argument set
             let (stdin, stdout, stderr, name, argc, envs) = try!(job_args(args, env));
             if env.hasf(&name) {
-                return Err(format!("Cannot run functions as jobs"));
+                return Err(WashError::runtime(format!("Cannot run functions as jobs")));
             } else {
                 id = try!(env.run_job_fd(stdin, stdout, stderr, &name, &argc, &envs));
             }
@@ -206,10 +208,10 @@ builtin!(job_func, args, env, {
 builtin!(job_output_func, args, env, {
     let arg = args.get(0);
     if !arg.is_flat() {
-        return Err("Give a job number".to_string());
+        return Err(WashError::runtime("Give a job number".to_string()));
     }
     let id = match from_str_radix(arg.flatten().as_slice(), 10) {
-        Err(e) => return Err(format!("Couldn't turn {} into a job number: {}", arg.flatten(), e)),
+        Err(e) => return Err(WashError::runtime(format!("Couldn't turn {} into a job number: {}", arg.flatten(), e))),
         Ok(num) => num
     };
     let out = try!(env.job_output(&id));
@@ -220,7 +222,7 @@ builtin!(job_output_func, args, env, {
             Some(v) if v != NL => s.push(v),
             _ => {}
         };
-        return Err(s);
+        return Err(WashError::runtime(s));
     } else {
         let mut s = String::from_utf8_lossy(out.output.as_slice()).into_owned();
         // remove trailing newlines
@@ -236,39 +238,55 @@ builtin!(directed_job_func, args, env, {
     return job_output_func(&try!(job_func(args, env)), env);
 });
 
-builtin!(run_func, args, env, {
-    let out;
+fn run_inner(args:&WashArgs, env:&mut WashEnv) -> WashResult<ProcessExit> {
     if args.is_empty() || args.len() < 1 {
-        return Err("No arguments given".to_string());
+        return Err(WashError::runtime("No arguments given".to_string()));
     } else if args.is_flat() {
         // easy case, no arguments to the function
         if env.hasf(&args.flatten()) {
             try!(env.runf(&args.flatten(), &Empty));
-            out = ExitStatus(0);
+            return Ok(ExitStatus(0));
         } else {
-            out = try!(env.run_command(&args.flatten(), &vec![]));
+            return env.run_command(&args.flatten(), &vec![]);
         }
     } else if !args.get(0).is_flat() {
-        return Err("Can only run flat names".to_string());
+        return Err(WashError::runtime("Can only run flat names".to_string()));
     } else if !FD_REGEX.is_match(args.get(0).flatten().as_slice()) &&
         !EQ_TEMP_REGEX.is_match(args.get(0).flatten().as_slice()) {
             // easy case, just a command
             let args_slice = args.slice(1, -1);
             if env.hasf(&args.get(0).flatten()) {
                 try!(env.runf(&args.get(0).flatten(), &args_slice));
-                out = ExitStatus(0);
+                return Ok(ExitStatus(0));
             } else {
-                out = try!(env.run_command(&args.get(0).flatten(), &args_slice.flatten_vec()));
+                return env.run_command(&args.get(0).flatten(), &args_slice.flatten_vec());
             }
         } else {
             // hard case, full argument set
             let (stdin, stdout, stderr, name, argc, envs) = try!(job_args(args, env));
             if env.hasf(&name) {
-                return Err(format!("Cannot redirect function output"));
+                return Err(WashError::runtime(format!("Cannot redirect function output")));
             } else {
-                out = try!(env.run_command_fd(stdin, stdout, stderr, &name, &argc, &envs));
+                return env.run_command_fd(stdin, stdout, stderr, &name, &argc, &envs);
             }
         }
+}
+
+builtin!(run_func, args, env, {
+    env.last_command = if args.is_flat() {
+        args.flatten()
+    } else {
+        args.get(0).flatten()
+    };
+    let out = match run_inner(args, env) {
+        Err(e) => {
+            // commands that couldn't be found exit like they do in sh
+            env.set_exit(ExitStatus(if e.kind == ErrorKind::Spawn {127} else {1}));
+            return Err(e);
+        },
+        Ok(v) => v
+    };
+    env.set_exit(out);
     return match out {
         ExitSignal(sig) => {
             return Ok(Long(vec![Flat("signal".to_string()),
@@ -284,7 +302,7 @@ builtin!(run_func, args, env, {
 builtin!(jobs_func, _, env, {
     let jobs = env.get_jobs();
     if jobs.len() == 0 {
-        return Err("No jobs".to_string());
+        return Err(WashError::runtime("No jobs".to_string()));
     } else {
         return Ok(env.get_jobs());
     }
@@ -299,18 +317,20 @@ builtin!(fg_func, args, env, {
         }
     } else {
         id = match from_str_radix(args.get(0).flatten().as_slice(), 10) {
-            Err(e) => return Err(format!("Not given a job number: {}", e)),
+            Err(e) => return Err(WashError::runtime(format!("Not given a job number: {}", e))),
             Ok(v) => v
         };
         if !env.has_job(&id) {
-            return Err(format!("Job not found"));
+            return Err(WashError::runtime(format!("Job not found")));
         }
     }
     let name = try!(env.get_job(&id)).command.clone();
     env.outf(format_args!("Returning to: {}\n", name));
+    env.last_command = name;
     try!(env.restart_job(&id));
     let out = try!(env.wait_job(&id));
     try!(env.remove_if_done(&id));
+    env.set_exit(out);
     return describe_process_output(&match out {
         ExitSignal(sig) => Long(vec![Flat("signal".to_string()),
                                      Flat(format!("{}", sig))]),
@@ -321,14 +341,14 @@ builtin!(fg_func, args, env, {
 
 builtin!(get_func, args, env, {
     if args.len() < 1 {
-        return Err("No variable name given".to_string());
+        return Err(WashError::runtime("No variable name given".to_string()));
     }
     let name = match args.get(0) {
         ref v if !v.is_flat() => {
-            return Err("Variable names can only be flat".to_string());
+            return Err(WashError::runtime("Variable names can only be flat".to_string()));
         },
         ref v if !EQ_VAR_REGEX.is_match(v.flatten().as_slice()) => {
-            return Err("Varibale names cannot contain whitespace, quotes, or parentheses".to_string());
+            return Err(WashError::runtime("Varibale names cannot contain whitespace, quotes, or parentheses".to_string()));
         }
         v => v.flatten()
     };
@@ -356,18 +376,18 @@ builtin!(setp_func, args, env, {
     } else {
         let path = match args.get(0) {
             ref v if !v.is_flat() => {
-                return Err("Variable paths can only be flat".to_string());
+                return Err(WashError::runtime("Variable paths can only be flat".to_string()));
             }
             v => v.flatten()
         };
         if path == "cfg" {
-            return Err(format!("Cannot set variable path to configuration variables"));
+            return Err(WashError::runtime(format!("Cannot set variable path to configuration variables")));
         } else if path == "sys" {
-            return Err(format!("Cannot set variable path to system variables"));
+            return Err(WashError::runtime(format!("Cannot set variable path to system variables")));
         } else if path == "env" {
-            return Err("Cannot set variable path to environment variables".to_string());
+            return Err(WashError::runtime("Cannot set variable path to environment variables".to_string()));
         } else if path == "pipe" {
-            return Err("Cannot set variable path to job pipes".to_string());
+            return Err(WashError::runtime("Cannot set variable path to job pipes".to_string()));
         } else {
             env.variables = path.clone();
             return Ok(Empty);
@@ -375,20 +395,39 @@ builtin!(setp_func, args, env, {
     }
 });
 
-builtin!(describe_process_output, args, _, {
+builtin!(describe_process_output, args, env, {
     let argv = args.flatten_vec();
-    if args.is_empty() {
-        return Err("Command Failed".to_string());
+    let name = if env.last_command.is_empty() {
+        "Command".to_string()
+    } else {
+        env.last_command.clone()
+    };
+    let message = if args.is_empty() {
+        format!("{} failed", name)
     } else if argv.len() < 2 {
-        return Err(format!("Command failed: {}", args.flatten()));
+        format!("{} failed: {}", name, args.flatten())
     } else if argv == vec!["signal", "19"] || // SIGSTOP
         argv == vec!["signal", "20"] { // SIGTSTP
-            return Err(format!("Command stopped"));
+            format!("{} stopped", name)
         } else if argv != vec!["status", "0"] {
-            return Err(format!("Command failed with {} {}", argv[0], argv[1]));
+            format!("{} failed with {} {}", name, argv[0], argv[1])
         } else {
             return Ok(Empty);
-        }
+        };
+    return Err(WashError::runtime(message));
+});
+
+builtin!(strict_process_output, args, env, {
+    // commands followed by &; only have to succeed in strict mode
+    if env.settings.flag("strict") {
+        return describe_process_output(args, env);
+    }
+    return Ok(Empty);
+});
+
+builtin!(ignore_process_output, _, _, {
+    // for commands after try!, which may fail
+    return Ok(Empty);
 });
 
 builtin!(run_failed_func, args, _, {
@@ -400,16 +439,16 @@ builtin!(run_failed_func, args, _, {
     }
 });
 
-builtin!(ftime_func, args, _, {
+builtin!(ftime_func, args, env, {
     let fmt = match args.get(0) {
         Flat(s) => s,
-        _ => return Err(format!("Time format must be flat"))
+        _ => return Err(WashError::runtime(format!("Time format must be flat")))
     };
     let lt = match get_time() {
-        None => return Err(format!("Could not get current time")),
+        None => return Err(WashError::runtime(format!("Could not get current time"))),
         Some(t) => t
     };
-    return Ok(Flat(strf_time(&fmt, &lt)));
+    return Ok(Flat(strf_time(&fmt, &lt, env.settings.number("strf_buf_size"))));
 });
 
 builtin!(dot_func, args, _, {
@@ -431,10 +470,15 @@ builtin!(subprompt_func, _, _, {
     return Ok(Flat(format!(" => $(")));
 });
 
+// shown against the right edge of the prompt's last line, nothing to start with
+builtin!(rprompt_func, _, _, {
+    return Ok(Flat(String::new()));
+});
+
 builtin!(open_output_func, args, env, {
     let fname = match args {
         &Flat(ref s) => s.clone(),
-        _ => return Err(format!("File name must be flat"))
+        _ => return Err(WashError::runtime(format!("File name must be flat")))
     };
     let fpath = expand_path(PathBuf::new(&fname));
     let fid = try!(env.output_file(&fpath));
@@ -444,44 +488,82 @@ builtin!(open_output_func, args, env, {
 builtin!(open_input_func, args, env, {
     let fname = match args {
         &Flat(ref s) => s.clone(),
-        _ => return Err(format!("File name must be flat"))
+        _ => return Err(WashError::runtime(format!("File name must be flat")))
     };
     let fpath = expand_path(PathBuf::new(&fname));
     let fid = try!(env.input_file(&fpath));
     Ok(Flat(format!("{}", fid)))
 });
 
+builtin!(exit_func, args, env, {
+    let status = match args.get(0) {
+        Empty => env.exit_status(),
+        Flat(ref s) => match from_str_radix(s.as_slice(), 10) {
+            Err(e) => return Err(WashError::runtime(format!("Not given an exit status: {}", e))),
+            Ok(v) => v
+        },
+        Long(_) => return Err(WashError::runtime(format!("Exit status must be flat")))
+    };
+    env.exit = Some(status);
+    // stop running whatever called this
+    return Err(WashError::stop());
+});
+
+builtin!(bind_func, args, env, {
+    // the last argument is the action, the ones before it are keys
+    let mut argv = args.flatten_vec();
+    match argv.len() {
+        0 => Ok(env.keymap.all_args()),
+        1 => env.keymap.get_args(argv[0].as_slice()),
+        _ => {
+            let action = argv.pop().unwrap();
+            try!(env.keymap.bind(argv.connect(" ").as_slice(), action.as_slice()));
+            Ok(Empty)
+        }
+    }
+});
+
+builtin!(unbind_func, args, env, {
+    try!(env.keymap.unbind(args.flatten_vec().connect(" ").as_slice()));
+    return Ok(Empty);
+});
+
 builtin!(builtins_func, _, _, {
     return Ok(Long(vec![
         Flat("$".to_string()),
+        Flat("bind".to_string()),
         Flat("builtins".to_string()),
         Flat("cd".to_string()),
         Flat("dot".to_string()),
+        Flat("exit".to_string()),
         Flat("fg".to_string()),
         Flat("get".to_string()),
         Flat("jobs".to_string()),
         Flat("run".to_string()),
         Flat("setp".to_string()),
-        Flat("source".to_string())]));
+        Flat("source".to_string()),
+        Flat("unbind".to_string())]));
 });
 
-pub fn load_builtins(env:&mut WashEnv) -> Result<WashArgs, String> {
-    // functions
-    try!(env.insfd("source", source_func));
-    try!(env.insfd("cd", cd_func));
-    try!(env.insfd("builtins", builtins_func));
-    try!(env.insfd("outs", outs_func));
+pub fn load_builtins(env:&mut WashEnv) -> WashResult<WashArgs> {
+    // functions, the ones added with insfc set $sys:status like commands
+    try!(env.insfc("source", source_func));
+    try!(env.insfc("cd", cd_func));
+    try!(env.insfd("exit", exit_func));
+    try!(env.insfc("builtins", builtins_func));
+    try!(env.insfc("outs", outs_func));
     try!(env.insfd("$", directed_job_func));
     try!(env.insfd("run", run_func));
     try!(env.insfd("get", get_func));
     try!(env.insfd("setp", setp_func));
-    try!(env.insfd("jobs", jobs_func));
-    try!(env.insfd("job", job_func));
+    try!(env.insfc("jobs", jobs_func));
+    try!(env.insfc("job", job_func));
     try!(env.insfd("fg", fg_func));
     try!(env.insfd("ftime", ftime_func));
     try!(env.insfd("dot", dot_func));
     try!(env.insfd("prompt", prompt_func));
     try!(env.insfd("subprompt", subprompt_func));
+    try!(env.insfd("rprompt", rprompt_func));
     try!(env.insfd("equal?", equal_func));
     try!(env.insfd("not?", not_func));
     try!(env.insfd("re_equal?", re_equal_func));
@@ -490,9 +572,13 @@ pub fn load_builtins(env:&mut WashEnv) -> Result<WashArgs, String> {
     try!(env.insfd("run_failed?", run_failed_func));
     try!(env.insfd("getall", getall_func));
     try!(env.insfd("flatten_eqlist", flatten_eqlist_func));
+    try!(env.insfc("bind", bind_func));
+    try!(env.insfc("unbind", unbind_func));
 
     // commands that aren't really meant to be called by users
     try!(env.insfd("describe_process_output", describe_process_output));
+    try!(env.insfd("strict_process_output", strict_process_output));
+    try!(env.insfd("ignore_process_output", ignore_process_output));
 
     return Ok(Empty);
 }
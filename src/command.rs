@@ -15,6 +15,8 @@ use controls::*;
 use constants::*;
 use termios::*;
 use signal::*;
+use ioctl::*;
+use error::*;
 
 // start off as null pointer
 static mut uglobal_term:*mut TermState = 0 as *mut TermState;
@@ -147,7 +149,10 @@ pub struct TermState {
     pub jobs: VecMap<Job>,
     files: VecMap<fs::File>,
     jobstack: Vec<usize>,
-    spawning: bool
+    spawning: bool,
+    // false when running scripts or not on a terminal,
+    // the terminal mode is left alone
+    pub interactive: bool
 }
 
 impl Drop for TermState {
@@ -167,8 +172,10 @@ impl Drop for TermState {
 impl TermState {
     pub fn new() -> TermState {
         let mut controls = Controls::new();
+        let interactive = is_tty(STDIN) && is_tty(STDOUT);
         let mut tios = match Termios::get() {
             Ok(t) => t,
+            Err(_) if !interactive => Termios::new(),
             Err(e) => {
                 controls.errf(format_args!("Warning: Could not get terminal mode: {}\n", e));
                 Termios::new()
@@ -184,11 +191,13 @@ impl TermState {
             jobs: VecMap::new(),
             files: VecMap::new(),
             jobstack: vec![],
-            spawning: false
+            spawning: false,
+            interactive: interactive
         }
     }
 
     pub fn update_terminal(&mut self) {
+        if !self.interactive {return}
         match Termios::set(&self.tios) {
             Err(e) =>
                 self.controls.errf(format_args!("Warning: Could not set terminal mode: {}\n", e)),
@@ -197,6 +206,7 @@ impl TermState {
     }
 
     pub fn restore_terminal(&mut self) {
+        if !self.interactive {return}
         match Termios::set(&self.old_tios) {
             Err(e) =>
                 self.controls.errf(format_args!("Warning: Could not restore terminal mode: {}\n", e)),
@@ -239,9 +249,9 @@ impl TermState {
         }
     }
 
-    pub fn remove_if_done(&mut self, id:&usize) -> Result<bool, String> {
+    pub fn remove_if_done(&mut self, id:&usize) -> WashResult<bool> {
         if !self.jobs.contains_key(id) {
-            return Err(format!("Job not found"));
+            return Err(WashError::runtime(format!("Job not found")));
         }
         if self.jobs.get(id).unwrap().check_exit() {
             self.jobs.remove(id);
@@ -287,9 +297,9 @@ impl TermState {
         return Ok(fid);
     }
 
-    pub fn get_job(&self, id:&usize) -> Result<&Job, String> {
+    pub fn get_job(&self, id:&usize) -> WashResult<&Job> {
         match self.jobs.get(id) {
-            None => Err("Job not found".to_string()),
+            None => Err(WashError::runtime("Job not found".to_string())),
             Some(job) => Ok(job)
         }
     }
@@ -298,9 +308,9 @@ impl TermState {
         self.jobstack.pop()
     }
 
-    pub fn restart_job(&mut self, id:&usize) -> Result<(), String> {
+    pub fn restart_job(&mut self, id:&usize) -> WashResult<()> {
         let mut job = match self.jobs.get_mut(id) {
-            None => return Err(format!("Job not found")),
+            None => return Err(WashError::runtime(format!("Job not found"))),
             Some(job) => job
         };
         tryf!(job.process.signal(SIGCONT as isize),
@@ -310,7 +320,7 @@ impl TermState {
 
     pub fn start_job(&mut self, stdin:StdioContainer, stdout:StdioContainer, stderr:StdioContainer,
                      name:&String, args:&Vec<String>,
-                     envs:&Vec<(String, Option<String>)>) -> Result<usize, String> {
+                     envs:&Vec<(String, Option<String>)>) -> WashResult<usize> {
         let mut process = Command::new(name);
         process.args(args.as_slice());
         process.stdin(stdin);
@@ -326,7 +336,10 @@ impl TermState {
         self.spawning = true;
         let out = process.spawn();
         self.spawning = false;
-        let child = tryf!(out, "Couldn't spawn {name}: {err}", name=name);
+        let child = match out {
+            Err(e) => return Err(WashError::spawn(format!("Couldn't spawn {}: {}", name, e))),
+            Ok(c) => c
+        };
         let id = self.find_jobs_hole();
         let mut job =  Job {
             command: name.clone(),
@@ -357,7 +370,7 @@ impl TermState {
         return Ok(id);
     }
 
-    fn wait_job_signal(&mut self, id:&usize, set:&SigSet) -> Result<ProcessExit, String> {
+    fn wait_job_signal(&mut self, id:&usize, set:&SigSet) -> WashResult<ProcessExit> {
         let mut info; let mut fields;
         loop {
             info = match signal_wait_set(set, None) {
@@ -366,7 +379,7 @@ impl TermState {
                     // our waiting was interrupted, try again
                     continue;
                 },
-                Err(e) => return Err(format!("Couldn't wait for child to exit: {}", e))
+                Err(e) => return Err(WashError::runtime(format!("Couldn't wait for child to exit: {}", e)))
             };
             match info.signo {
                 SIGINT => {
@@ -394,7 +407,7 @@ impl TermState {
                 SIGCHLD => {
                     fields = match info.determine_sigfields() {
                         SigFields::SigChld(f) => f,
-                        _ => return Err(format!("Caught signal {} instead of SIGCHLD", info.signo))
+                        _ => return Err(WashError::runtime(format!("Caught signal {} instead of SIGCHLD", info.signo)))
                     };
                     if fields.pid == self.jobs.get_mut(id).unwrap().process.id() {
                         // process of interest died
@@ -422,14 +435,14 @@ impl TermState {
                             }
                         }
                     }
-                }, _ => return Err(format!("Caught unexpected signal: {}", info.signo))
+                }, _ => return Err(WashError::runtime(format!("Caught unexpected signal: {}", info.signo)))
             }
         }
     }
 
-    pub fn wait_job(&mut self, id:&usize) -> Result<ProcessExit, String> {
+    pub fn wait_job(&mut self, id:&usize) -> WashResult<ProcessExit> {
         if !self.jobs.contains_key(id) {
-            return Err("Job not found".to_string());
+            return Err(WashError::runtime("Job not found".to_string()));
         }
         if self.jobs.get(id).unwrap().check_exit() {
             // child has already exited
@@ -453,17 +466,17 @@ impl TermState {
         return out;
     }
 
-    pub fn job_output(&mut self, id:&usize) -> Result<ProcessOutput, String> {
+    pub fn job_output(&mut self, id:&usize) -> WashResult<ProcessOutput> {
         // set the foreground job (before borrowing self)
         let status = try!(self.wait_job(id));
         let mut child = self.jobs.remove(id).unwrap();
         let stdout = match child.process.stdout.as_mut() {
-            None => return Err("Child had no stdout".to_string()),
+            None => return Err(WashError::runtime("Child had no stdout".to_string())),
             Some(st) => tryf!(st.read_to_end(),
                               "Could not read stdout: {err}")
         };
         let stderr = match child.process.stderr.as_mut() {
-            None => return Err("Child had no stderr".to_string()),
+            None => return Err(WashError::runtime("Child had no stderr".to_string())),
             Some(st) => tryf!(st.read_to_end(),
                               "Could not read stderr: {err}")
         };
@@ -476,7 +489,7 @@ impl TermState {
 
     pub fn start_command(&mut self, stdin:StdioContainer, stdout:StdioContainer, stderr:StdioContainer,
                          name:&String, args:&Vec<String>,
-                         envs:&Vec<(String, Option<String>)>) -> Result<ProcessExit, String> {
+                         envs:&Vec<(String, Option<String>)>) -> WashResult<ProcessExit> {
         // set terminal settings for process
         // do this before we spawn the process
         self.restore_terminal();
@@ -505,7 +518,7 @@ impl TermState {
 
     pub fn run_job_fd(&mut self, stdin:Option<Fd>, stdout:Option<Fd>, stderr:Option<Fd>,
                       name:&String, args:&Vec<String>,
-                      envs:&Vec<(String, Option<String>)>) -> Result<usize, String> {
+                      envs:&Vec<(String, Option<String>)>) -> WashResult<usize> {
         let stdin_o = match stdin {
             Some(fd) => InheritFd(fd),
             None => CreatePipe(true, false)
@@ -521,14 +534,14 @@ impl TermState {
         self.start_job(stdin_o, stdout_o, stderr_o, name, args, envs)
     }
     
-    pub fn run_job(&mut self, name:&String, args:&Vec<String>) -> Result<usize, String> {
+    pub fn run_job(&mut self, name:&String, args:&Vec<String>) -> WashResult<usize> {
         // run the job directed
         self.run_job_fd(None, None, None, name, args, &vec![])
     }
 
     pub fn run_command_fd(&mut self, stdin:Option<Fd>, stdout:Option<Fd>, stderr:Option<Fd>,
                           name:&String, args:&Vec<String>,
-                          envs:&Vec<(String, Option<String>)>) -> Result<ProcessExit, String> {
+                          envs:&Vec<(String, Option<String>)>) -> WashResult<ProcessExit> {
         // commands can only run on existing pipes
         // to run a command on a new one, use a job
         let stdin_o = match stdin {
@@ -549,7 +562,7 @@ impl TermState {
         return out;
     }
     
-    pub fn run_command(&mut self, name:&String, args:&Vec<String>) -> Result<ProcessExit, String> {
+    pub fn run_command(&mut self, name:&String, args:&Vec<String>) -> WashResult<ProcessExit> {
         // run the command on stdin/out/err
         self.run_command_fd(None, None, None, name, args, &vec![])
     }
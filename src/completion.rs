@@ -0,0 +1,479 @@
+// Tab completion: working out what's being completed from the
+// InputLine, and finding candidates for it
+use std::fs::{self, PathExt};
+use std::path::{Path, PathBuf};
+use std::env;
+use std::cmp::Ordering;
+
+use input::*;
+use types::*;
+use types::InputValue::*;
+use env::*;
+use util::*;
+use fuzzy::*;
+use graph::*;
+use graph::Completion::*;
+use ioctl::*;
+use bash::*;
+
+use self::WordKind::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum WordKind {
+    // the first word on a line
+    Command,
+    // anything after the command, or inside a function call
+    Argument,
+    // a word starting with $
+    Variable
+}
+
+pub struct CompletionContext {
+    // the word being completed, up to the cursor
+    pub word: String,
+    pub kind: WordKind,
+    // command or function the word is an argument of
+    pub command: String,
+    // words on the line so far, ending with the one being completed
+    pub words: Vec<String>,
+    // the word is inside quotes
+    pub literal: bool
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Candidate {
+    // replaces the word being completed
+    pub text: String,
+    // candidates that can be added to, like directories,
+    // aren't followed by a space
+    pub complete: bool
+}
+
+// sources are asked for candidates for every context,
+// and return nothing for those they don't handle
+pub type CompletionSource = fn(&CompletionContext, &mut Completer, &WashEnv) -> Vec<Candidate>;
+
+pub struct Completer {
+    sources: Vec<CompletionSource>,
+    // learned from the lines that have been run
+    pub graph: Graph,
+    pub bash: BashCompleter
+}
+
+impl Candidate {
+    pub fn new(text:String, complete:bool) -> Candidate {
+        Candidate {
+            text: text,
+            complete: complete
+        }
+    }
+}
+
+impl Completer {
+    pub fn new() -> Completer {
+        let mut completer = Completer {
+            sources: vec![],
+            graph: Graph::new(),
+            bash: BashCompleter::new()
+        };
+        completer.add_source(command_source);
+        completer.add_source(file_source);
+        completer.add_source(variable_source);
+        completer.add_source(job_source);
+        completer.add_source(bash_source);
+        return completer;
+    }
+
+    pub fn add_source(&mut self, source:CompletionSource) {
+        self.sources.push(source);
+    }
+
+    // candidates best first. What the graph learned comes before
+    // the sources, in the order its weights put it
+    pub fn complete(&mut self, context:&CompletionContext, env:&WashEnv) -> Vec<Candidate> {
+        let learned = graph_source(context, self, env);
+        let sources = self.sources.clone();
+        let mut others = vec![];
+        for source in sources.iter() {
+            others.push_all(source(context, self, env).as_slice());
+        }
+        return rank_candidates(context.word.as_slice(), learned, others);
+    }
+}
+
+// put candidates in the order they're offered: better matches first,
+// then learned ones in the order they came, then shorter ones. Ones
+// starting with the word are preferred, the rest only show up if
+// there aren't any
+pub fn rank_candidates(word:&str, learned:Vec<Candidate>, others:Vec<Candidate>) -> Vec<Candidate> {
+    // candidates, and their place in learned
+    let mut found:Vec<(Candidate, Option<usize>)> = vec![];
+    for (i, candidate) in learned.into_iter().enumerate() {
+        found.push((candidate, Some(i)));
+    }
+    for candidate in others.into_iter() {
+        match found.iter().position(|&(ref c, _)| c.text == candidate.text) {
+            // sources know better about things like directories
+            Some(i) => found[i].0.complete = found[i].0.complete && candidate.complete,
+            None => found.push((candidate, None))
+        }
+    }
+    if found.iter().any(|&(ref c, _)| c.text.starts_with(word)) {
+        found.retain(|&(ref c, _)| c.text.starts_with(word));
+    }
+    let mut scored:Vec<(isize, Candidate, Option<usize>)> = vec![];
+    for (candidate, place) in found.into_iter() {
+        match fuzzy_match(word, candidate.text.as_slice()) {
+            None => {},
+            Some(m) => scored.push((m.score, candidate, place))
+        }
+    }
+    scored.sort_by(|&(asc, ref a, ap), &(bsc, ref b, bp)| {
+        match bsc.cmp(&asc) {
+            Ordering::Equal => match (ap, bp) {
+                (Some(ai), Some(bi)) => ai.cmp(&bi),
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => a.text.len().cmp(&b.text.len())
+            },
+            o => o
+        }
+    });
+    return scored.into_iter().map(|(_, c, _)| c).collect();
+}
+
+fn flat_words(values:&Vec<InputValue>) -> Vec<String> {
+    let mut out = vec![];
+    for value in values.iter() {
+        match value {
+            &Short(ref s) | &Literal(ref s) => out.push(s.clone()),
+            &Function(ref n, _) => out.push(n.clone()),
+            _ => {}
+        }
+    }
+    return out;
+}
+
+// work out what the word at the cursor is, and where it is
+pub fn completion_context(line:&InputLine) -> Option<CompletionContext> {
+    let (word, literal) = match line.front {
+        Short(ref s) => (s.clone(), false),
+        Literal(ref s) => (s.clone(), true),
+        Split(_) => (String::new(), false),
+        // right after a closing parenthesis
+        Long(_) | Function(_, _) => return None
+    };
+    let mut words;
+    let command;
+    let kind;
+    match line.back.last() {
+        Some(&Function(ref name, ref args)) => {
+            // arguments to a function call
+            words = flat_words(args);
+            command = name.clone();
+            kind = Argument;
+        },
+        Some(&Long(ref v)) => {
+            words = flat_words(v);
+            if words.is_empty() && line.back.len() == 1 {
+                command = String::new();
+                kind = Command;
+            } else {
+                command = match words.first() {
+                    None => String::new(),
+                    Some(s) => s.clone()
+                };
+                kind = Argument;
+            }
+        },
+        _ => return None
+    }
+    words.push(word.clone());
+    Some(CompletionContext {
+        kind: if !literal && word.starts_with("$") {Variable} else {kind},
+        word: word,
+        command: command,
+        words: words,
+        literal: literal
+    })
+}
+
+// sources keep anything the start of a word fuzzily matches
+pub fn matches(start:&str, name:&str) -> bool {
+    fuzzy_match(start, name).is_some()
+}
+
+// longest start shared by all candidates
+pub fn common_prefix(candidates:&Vec<Candidate>) -> String {
+    let mut prefix = match candidates.first() {
+        None => return String::new(),
+        Some(c) => c.text.clone()
+    };
+    for candidate in candidates.iter() {
+        while !candidate.text.starts_with(prefix.as_slice()) {
+            prefix.pop();
+        }
+    }
+    return prefix;
+}
+
+fn command_source(context:&CompletionContext, _:&mut Completer, env:&WashEnv) -> Vec<Candidate> {
+    if context.kind != Command || context.word.contains("/") {
+        // paths are left to file_source
+        return vec![];
+    }
+    let mut out = vec![];
+    for name in env.functions.keys() {
+        if matches(context.word.as_slice(), name.as_slice()) {
+            out.push(Candidate::new(name.clone(), true));
+        }
+    }
+    let path = match env::var("PATH") {
+        Err(_) => return out,
+        Ok(p) => p
+    };
+    for dir in path.as_slice().split(':') {
+        let entries = match fs::read_dir(Path::new(dir)) {
+            Err(_) => continue,
+            Ok(e) => e
+        };
+        for entry in entries {
+            // everything in $PATH is assumed to be executable
+            let path = match entry {
+                Err(_) => continue,
+                Ok(e) => e.path()
+            };
+            let name = match path.file_name() {
+                None => continue,
+                Some(n) => match n.to_str() {
+                    None => continue,
+                    Some(s) => s.to_string()
+                }
+            };
+            if matches(context.word.as_slice(), name.as_slice()) {
+                out.push(Candidate::new(name, true));
+            }
+        }
+    }
+    return out;
+}
+
+fn file_source(context:&CompletionContext, _:&mut Completer, _:&WashEnv) -> Vec<Candidate> {
+    if context.kind == Variable ||
+        (context.kind == Command && !context.word.contains("/")) {
+        return vec![];
+    }
+    // split into the directory and the start of the name in it
+    let (dir, start) = match context.word.rfind('/') {
+        None => (String::new(), context.word.clone()),
+        Some(i) => (context.word[..i + 1].to_string(), context.word[i + 1..].to_string())
+    };
+    let search = if dir.is_empty() {
+        PathBuf::new(".")
+    } else {
+        expand_path(PathBuf::new(dir.as_slice()))
+    };
+    let entries = match fs::read_dir(search.as_path()) {
+        Err(_) => return vec![],
+        Ok(e) => e
+    };
+    let mut out = vec![];
+    for entry in entries {
+        let path = match entry {
+            Err(_) => continue,
+            Ok(e) => e.path()
+        };
+        let name = match path.file_name() {
+            None => continue,
+            Some(n) => match n.to_str() {
+                None => continue,
+                Some(s) => s.to_string()
+            }
+        };
+        if name.starts_with(".") && !start.starts_with(".") {
+            // hidden files need to be asked for
+            continue;
+        }
+        if matches(start.as_slice(), name.as_slice()) {
+            if path.is_dir() {
+                out.push(Candidate::new(format!("{}{}/", dir, name), false));
+            } else {
+                out.push(Candidate::new(format!("{}{}", dir, name), true));
+            }
+        }
+    }
+    return out;
+}
+
+// system variables can't all be listed, so they're named here
+const SYS_VARIABLES:[&'static str; 9] = ["login", "hostname", "args", "status",
+                                         "error", "signal", "mode", "cwd", "scwd"];
+
+fn variable_source(context:&CompletionContext, _:&mut Completer, env:&WashEnv) -> Vec<Candidate> {
+    if context.kind != Variable {
+        return vec![];
+    }
+    let word = &context.word[1..];
+    let mut out = vec![];
+    match word.find(':') {
+        Some(i) => {
+            // $path:name
+            let path = word[..i].to_string();
+            let start = &word[i + 1..];
+            let names:Vec<String> = if path == "sys" {
+                SYS_VARIABLES.iter().map(|s| s.to_string()).collect()
+            } else {
+                match env.getallp(&path) {
+                    Ok(WashArgs::Long(v)) => v.iter().map(|item| item.get(0).flatten()).collect(),
+                    _ => vec![]
+                }
+            };
+            for name in names.iter() {
+                if matches(start, name.as_slice()) {
+                    out.push(Candidate::new(format!("${}:{}", path, name), true));
+                }
+            }
+        },
+        None => {
+            // variables on the current path, then paths themselves
+            match env.getall() {
+                Ok(WashArgs::Long(v)) => for item in v.iter() {
+                    let name = item.get(0).flatten();
+                    if matches(word, name.as_slice()) {
+                        out.push(Candidate::new(format!("${}", name), true));
+                    }
+                },
+                _ => {}
+            }
+            let mut paths:Vec<String> = env.paths.keys().map(|k| k.clone()).collect();
+            for special in ["sys", "env", "cfg", "pipe"].iter() {
+                paths.push(special.to_string());
+            }
+            for path in paths.iter() {
+                if !path.is_empty() && matches(word, path.as_slice()) {
+                    out.push(Candidate::new(format!("${}:", path), false));
+                }
+            }
+        }
+    }
+    return out;
+}
+
+fn job_source(context:&CompletionContext, _:&mut Completer, env:&WashEnv) -> Vec<Candidate> {
+    if context.kind != Argument || context.command != "fg" {
+        return vec![];
+    }
+    let mut out = vec![];
+    for id in env.term.jobs.keys() {
+        let id = format!("{}", id);
+        if matches(context.word.as_slice(), id.as_slice()) {
+            out.push(Candidate::new(id, true));
+        }
+    }
+    return out;
+}
+
+// arguments to commands bash knows how to complete
+fn bash_source(context:&CompletionContext, completer:&mut Completer, env:&WashEnv) -> Vec<Candidate> {
+    let script = env.settings.text("bash_completion");
+    if context.kind != Argument || script.is_empty() ||
+        context.words.first() != Some(&context.command) ||
+        env.functions.contains_key(&context.command) {
+        // not a command bash could know about
+        return vec![];
+    }
+    let mut out = vec![];
+    for reply in completer.bash.complete(script.as_slice(), &context.words).iter() {
+        if matches(context.word.as_slice(), reply.as_slice()) {
+            out.push(Candidate::new(reply.clone(), !reply.ends_with("/")));
+        }
+    }
+    return out;
+}
+
+// commands run in this directory before, newest first, and
+// arguments given to the command before, most common first
+fn graph_source(context:&CompletionContext, completer:&mut Completer, _:&WashEnv) -> Vec<Candidate> {
+    let graph = &completer.graph;
+    let found = match context.kind {
+        Command => {
+            let cwd = match env::current_dir() {
+                Err(_) => return vec![],
+                Ok(p) => format!("{}", p.display())
+            };
+            match graph.find(NodeKind::Directory, cwd.as_slice()) {
+                None => return vec![],
+                Some(dir) => graph.complete(dir, NodeKind::Command, &MinComplete(time_weight),
+                                            get_time_ms(), context.word.as_slice())
+            }
+        },
+        Argument if !context.literal => {
+            match graph.find(NodeKind::Command, context.command.as_slice()) {
+                None => return vec![],
+                Some(command) => graph.complete(command, NodeKind::Argument, &MaxComplete(frequency_weight),
+                                                get_time_ms(), context.word.as_slice())
+            }
+        },
+        _ => return vec![]
+    };
+    return found.iter().map(|id| Candidate::new(graph.get(*id).text.clone(), true)).collect();
+}
+
+#[test]
+fn common_prefix_test() {
+    let candidates = vec![Candidate::new("source".to_string(), true),
+                          Candidate::new("sort".to_string(), true),
+                          Candidate::new("sox".to_string(), true)];
+    assert_eq!(common_prefix(&candidates), "so".to_string());
+    assert_eq!(common_prefix(&vec![]), String::new());
+}
+
+#[test]
+fn completion_context_test() {
+    let mut line = InputLine::new();
+    for ch in "ech".chars() {
+        line.push(ch);
+    }
+    let context = completion_context(&line).unwrap();
+    assert_eq!(context.kind, Command);
+    assert_eq!(context.word, "ech".to_string());
+    for ch in "o $sys:lo".chars() {
+        line.push(ch);
+    }
+    let context = completion_context(&line).unwrap();
+    assert_eq!(context.kind, Variable);
+    assert_eq!(context.command, "echo".to_string());
+    assert_eq!(context.words, vec!["echo".to_string(), "$sys:lo".to_string()]);
+}
+
+#[test]
+fn rank_candidates_test() {
+    let mut graph = Graph::new();
+    for &(line, time) in [("git push", 10), ("git push", 20), ("git pull", 30)].iter() {
+        graph.record(&::history::HistoryEntry {
+            text: line.to_string(),
+            time: time,
+            cwd: "/src".to_string(),
+            status: 0,
+            duration: 0
+        });
+    }
+    let git = graph.find(NodeKind::Command, "git").unwrap();
+    let learned = |completion:Completion| -> Vec<Candidate> {
+        graph.complete(git, NodeKind::Argument, &completion, 100, "pu").iter()
+            .map(|id| Candidate::new(graph.get(*id).text.clone(), true)).collect()
+    };
+    let others = vec![Candidate::new("pu".to_string(), true),
+                      Candidate::new("pull".to_string(), true)];
+    let texts = |candidates:Vec<Candidate>| -> Vec<String> {
+        candidates.into_iter().map(|c| c.text).collect()
+    };
+    // the weights decide between equal matches, ahead of what the sources found
+    assert_eq!(texts(rank_candidates("pu", learned(MaxComplete(frequency_weight)), others.clone())),
+               vec!["push".to_string(), "pull".to_string(), "pu".to_string()]);
+    assert_eq!(texts(rank_candidates("pu", learned(MinComplete(time_weight)), others.clone())),
+               vec!["pull".to_string(), "push".to_string(), "pu".to_string()]);
+    // and come first when nothing has been typed
+    assert_eq!(texts(rank_candidates("", learned(MaxComplete(frequency_weight)), vec![])),
+               vec!["push".to_string(), "pull".to_string()]);
+}
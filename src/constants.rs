@@ -4,14 +4,11 @@
 use libc::*;
 use regex::Regex;
 
-// Settings which will eventually make their way into some type of
-// configuration interface
-pub const HISTORY_SIZE:usize = 500;
+pub use termconst::*;
 
-// standard file descriptiors
-pub const STDIN:c_int  = 0;
-pub const STDOUT:c_int = 1;
-pub const STDERR:c_int = 2;
+// Defaults for settings, see settings.rs
+pub const HISTORY_SIZE:usize = 500;
+pub const HISTORY_PATH:&'static str = "~/.wash_history";
 
 pub const NFD_BITS:usize = 8 * 8; // 8 * sizeof(long int)
 pub const FD_SET_SIZE:usize = 1024 / NFD_BITS;
@@ -23,6 +20,7 @@ pub const NL:char  = '\n';
 pub const ESC:char = '\u{1b}';
 pub const ANSI:char = '\u{5b}'; // [
 pub const BS:char = '\u{8}';
+pub const TAB:char = '\t';
 pub const SPC:char = ' ';
 pub const CMA:char = ',';
 pub const QUT:char = '\"';
@@ -32,17 +30,21 @@ pub const BEL:char = '\u{7}';
 pub const CTA:char = '\u{1}';
 pub const CTE:char = '\u{5}';
 pub const CTK:char = '\u{b}';
+pub const CTG:char = '\u{7}';
+pub const CTR:char = '\u{12}';
 // we specifically need a constant for "cursor right"
 pub const CRSR_RIGHT:&'static str = "\u{1b}\u{5b}C";
 pub const ANSI_BEGIN:&'static str = "\u{1b}\u{5b}";
 pub const CRSR_POS:&'static str = "\u{1b}\u{5b}6n";
-
-// select termios constants that we use
-pub const ICANON:c_uint   = 2;
-pub const ECHO:c_uint     = 8;
-pub const TCSANOW:c_int   = 0;
-pub const TCSADRAIN:c_int = 1;
-pub const TCSAFLUSH:c_int = 2;
+// save and restore where the cursor is, without asking the terminal
+pub const CRSR_SAVE:&'static str = "\u{1b}7";
+pub const CRSR_RESTORE:&'static str = "\u{1b}8";
+// bracketed paste, where the terminal marks text that was pasted
+pub const PASTE_ON:&'static str = "\u{1b}\u{5b}?2004h";
+pub const PASTE_OFF:&'static str = "\u{1b}\u{5b}?2004l";
+// select graphic rendition codes
+pub const STYLE_RESET:&'static str = "0";
+pub const STYLE_REVERSE:&'static str = "7";
 
 // signal values
 //pub const SIGHUP:c_int    = 1;       /* Hangup (POSIX).  */
@@ -93,6 +95,18 @@ pub const SIG_BLOCK:c_int = 0;
 pub const SIG_UNBLOCK:c_int = 1;
 pub const SIG_SETMASK:c_int = 2;
 
+// glibc has no wrapper for this one, and its number depends on the
+// architecture. None where it isn't known
+#[cfg(target_arch = "x86_64")]
+pub const SYS_RT_SIGQUEUEINFO:Option<c_long> = Some(129);
+#[cfg(target_arch = "aarch64")]
+pub const SYS_RT_SIGQUEUEINFO:Option<c_long> = Some(138);
+#[cfg(any(target_arch = "x86", target_arch = "arm"))]
+pub const SYS_RT_SIGQUEUEINFO:Option<c_long> = Some(178);
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64",
+              target_arch = "x86", target_arch = "arm")))]
+pub const SYS_RT_SIGQUEUEINFO:Option<c_long> = None;
+
 pub const SA_NOCLDSTOP:c_int = 1;
 pub const SA_NOCLDWAIT:c_int = 2;
 pub const SA_SIGINFO:c_int   = 4;
@@ -107,7 +121,22 @@ pub const WASH_RUN_SYMBOL:&'static str = "wash_run";
 pub const WASH_LOAD_SYMBOL:&'static str = "wash_load";
 pub const WO_PATH:&'static str = "/tmp/wash/";
 
-pub const NCCS:usize = 32;
+// startup files, run in this order before the first prompt
+pub const SYSTEM_RC_PATH:&'static str = "/etc/washrc";
+pub const USER_RC_PATH:&'static str = "~/.washrc";
+
+// bash completion, loaded by a helper bash to complete arguments
+// (can be changed or turned off with $cfg:bash_completion)
+pub const BASH_COMPLETION_PATH:&'static str = "/usr/share/bash-completion/bash_completion";
+// milliseconds to wait for the helper before giving up
+pub const BASH_TIMEOUT:usize = 1000;
+
+// select graphic rendition codes for each kind of highlighted text
+pub const DEFAULT_COLORS:&'static str =
+    "command=32 missing=31 literal=33 variable=36 keyword=35 unbalanced=1;41 suggestion=2";
+
+// killed text the line editor keeps for yanking
+pub const KILL_RING_SIZE:usize = 30;
 
 // ioctls
 pub const TIOCGWINSZ:c_ulong = 0x00005413;
@@ -119,17 +148,17 @@ pub static EQ_VAR_REGEX:Regex = regex!("^[^ \t\r\n\"()]+$");
 pub static EQ_PATH_REGEX:Regex = regex!("^([^ \t\r\n\"():]*):([^ \t\r\n\"():]+)$");
 pub static EQ_TEMP_REGEX:Regex = regex!("^([^ \t\r\n\"():]*):?([^ \t\r\n\"():]+):$");
 pub static FD_REGEX:Regex = regex!("^@([^ \t\r\n\"():01234567890]*):?([\\d]+)$");
-pub static PPOS_REGEX:Regex = regex!("^\\[(\\d+);(\\d+)$");
 pub static NL_REGEX:Regex = regex!("\n");
 
-// stop error
-pub const STOP:&'static str = "stop";
-
 pub const MAX_ESCAPE:usize = 15;
+// milliseconds to wait for the rest of an escape sequence before
+// taking it as ESC on its own
+pub const ESCAPE_TIMEOUT:usize = 50;
 
 pub const HOST_NAME_MAX:usize = 64;
 
 // strf needs an arbitrary buffer, hopefully 200 characters is big enough
+// (can be changed with $cfg:strf_buf_size)
 pub const STRF_BUF_SIZE:usize = 200;
 
 #[test]
@@ -315,11 +315,15 @@ impl Controls {
         if ch == NL {
             self.new_line();
         } else {
-            if self.grow_check(1) {
+            let width = char_width(ch);
+            if width == 0 {
+                // combines with the character before it
+                return;
+            } else if self.grow_check(width) {
                 self.stdout.write_char(SPC).unwrap();
                 self.stdout.write_char(DEL).unwrap();
             }
-            self.grow(1);
+            self.grow(width);
         }
     }
 
@@ -332,20 +336,20 @@ impl Controls {
         match splits.next() {
             Some(part) => {
                 self.stdout.write_str(part).unwrap();
-                if self.cursor.col + part.len() - 1 == self.tsize.col as usize {
+                if self.cursor.col + visible_width(part) - 1 == self.tsize.col as usize {
                     self.stdout.write_char(SPC).unwrap();
                     self.stdout.write_char(DEL).unwrap();
                 }
-                self.grow(part.len());
+                self.grow(visible_width(part));
                 for part in splits {
                     self.new_line();
                     self.stdout.write_char(NL).unwrap();
                     self.stdout.write_str(part).unwrap();
-                    if self.cursor.col + part.len() - 1 == self.tsize.col as usize {
+                    if self.cursor.col + visible_width(part) - 1 == self.tsize.col as usize {
                         self.stdout.write_char(SPC).unwrap();
                         self.stdout.write_char(DEL).unwrap();
                     }
-                    self.grow(part.len());
+                    self.grow(visible_width(part));
                 }
             },
             _ => {}
@@ -365,13 +369,13 @@ impl Controls {
         let mut splits = NL_REGEX.split(s);
         match splits.next() {
             Some(part) => {
-                self.grow(part.len());
+                self.grow(visible_width(part));
                 for part in splits {
                     self.new_row();
-                    if self.cursor.col + part.len() == self.tsize.col as usize {
+                    if self.cursor.col + visible_width(part) == self.tsize.col as usize {
                         self.stdout.write_char(NL).unwrap();
                     }
-                    self.grow(part.len());
+                    self.grow(visible_width(part));
                 }
             },
             _ => {}
@@ -382,13 +386,14 @@ impl Controls {
         self.err(fmt::format(args).as_slice());
     }
 
-    pub fn del(&mut self) {
-        if self.tsize.col == 0 || self.tsize.row == 0 {return}
-        if self.cursor.col > 1 {
-            self.stdout.write_char(DEL).unwrap();
-            self.shrink(1);
+    // back over a character that took up width columns
+    pub fn del(&mut self, width:usize) {
+        if self.tsize.col == 0 || self.tsize.row == 0 || width == 0 {return}
+        if self.cursor.col > width {
+            self.stdout.write_str(build_string(DEL, width).as_slice()).unwrap();
+            self.shrink(width);
         } else {
-            self.shrink(1);
+            self.shrink(width);
             self.move_to_pointer();
         }
     }
@@ -493,6 +498,31 @@ impl Controls {
         self.move_to(old);
     }
 
+    // clear from the cursor to the end of the screen
+    pub fn clear_below(&mut self) {
+        self.stdout.write_str(ANSI_BEGIN).unwrap();
+        self.stdout.write_char('J').unwrap();
+    }
+
+    // straight up, for going back over the lines of a prompt
+    pub fn rows_up(&mut self, by:usize) {
+        if by == 0 || self.tsize.col == 0 || self.tsize.row == 0 {return}
+        self.stdout.write_fmt(format_args!("{}{}A", ANSI_BEGIN, by)).unwrap();
+        self.cursor.row = if self.cursor.row > by {self.cursor.row - by} else {1};
+    }
+
+    // write s against the right edge of the row the cursor is on,
+    // and put the cursor back where it was. None of this is tracked
+    pub fn outs_right(&mut self, s:&str) {
+        let width = visible_width(s);
+        if self.tsize.col == 0 || self.tsize.row == 0 || width >= self.width() {return}
+        let col = self.width() - width + 1;
+        self.stdout.write_str(CRSR_SAVE).unwrap();
+        self.stdout.write_fmt(format_args!("{}{}G", ANSI_BEGIN, col)).unwrap();
+        self.stdout.write_str(s).unwrap();
+        self.stdout.write_str(CRSR_RESTORE).unwrap();
+    }
+
     pub fn next_start(&mut self) {
         if self.tsize.col == 0 || self.tsize.row == 0 {return}
         self.new_row();
@@ -503,6 +533,17 @@ impl Controls {
         });
     }
 
+    // clear the rest of the row from by columns after the cursor,
+    // without moving it
+    pub fn clear_after(&mut self, by:usize) {
+        self.stdout.write_str(CRSR_SAVE).unwrap();
+        if by > 0 {
+            self.stdout.write_fmt(format_args!("{}{}C", ANSI_BEGIN, by)).unwrap();
+        }
+        self.stdout.write_fmt(format_args!("{}K", ANSI_BEGIN)).unwrap();
+        self.stdout.write_str(CRSR_RESTORE).unwrap();
+    }
+
     pub fn flush(&mut self) {
         self.stdout.flush().unwrap();
         self.stderr.flush().unwrap();
@@ -512,6 +553,10 @@ impl Controls {
         self.stdout.write_str(CRSR_POS).unwrap();
     }
 
+    pub fn bracketed_paste(&mut self, on:bool) {
+        self.stdout.write_str(if on {PASTE_ON} else {PASTE_OFF}).unwrap();
+    }
+
     fn move_to_pointer(&mut self) {
         let (row, col) = (self.cursor.row, self.cursor.col);
         self.stdout.write_fmt(format_args!("{}{};{}f", ANSI_BEGIN, row, col)).unwrap();
@@ -522,6 +567,11 @@ impl Controls {
         self.move_to_pointer();
     }
 
+    // change how text is drawn, this doesn't move the cursor
+    pub fn style(&mut self, code:&str) {
+        self.stdout.write_fmt(format_args!("{}{}m", ANSI_BEGIN, code)).unwrap();
+    }
+
     pub fn bell(&mut self) {
         self.stdout.write_char(BEL).unwrap();
     }
@@ -21,14 +21,19 @@ use signal::*;
 use constants::*;
 use ioctl::*;
 use util::*;
+use error::*;
+use settings::*;
+use bindings::*;
 
 use self::FuncEntry::*;
 
 // !!!
 // Wash function calling convention
-pub type WashFunc = fn(&WashArgs, &mut WashEnv) -> Result<WashArgs, String>;
+pub type WashFunc = fn(&WashArgs, &mut WashEnv) -> WashResult<WashArgs>;
 pub enum FuncEntry {
     Direct(WashFunc),
+    // builtins used like commands, which set $sys:status as a command would
+    Command(WashFunc),
     Indirect(SectionTable)
 }
 
@@ -42,8 +47,8 @@ pub type PathTable = HashMap<String, VarTable>;
 
 // WashLoad returns two lists, the first of initialized functions,
 // the second the same of variables
-type WashLoad = extern fn(*const WashArgs, *mut WashEnv) -> Result<WashArgs, String>;
-type WashRun = extern fn(*const WashArgs, *mut WashEnv) -> Result<WashArgs, String>;
+type WashLoad = extern fn(*const WashArgs, *mut WashEnv) -> WashResult<WashArgs>;
+type WashRun = extern fn(*const WashArgs, *mut WashEnv) -> WashResult<WashArgs>;
 
 // global stop check
 static mut uexec_stop:bool = false;
@@ -73,6 +78,10 @@ unsafe extern fn env_sigint(_:c_int, _:*const SigInfo,
 pub struct ASTRunner {
     sections: SectionTable,
     position: SectionType,
+    // function being run, empty for top-level code
+    name: String,
+    // line of the last Mark seen
+    pos: Option<SourcePos>
 }
 
 pub struct WashEnv {
@@ -81,6 +90,22 @@ pub struct WashEnv {
     pub functions: FuncTable,
     pub scripts: ScriptTable,
     pub term: TermState,
+    pub settings: Settings,
+    // key bindings for the line editor
+    pub keymap: Keymap,
+    // the line editor's mode, for $sys:mode
+    pub edit_mode: String,
+    pub args: Vec<String>,
+    // exit of the last command run, and the status wash
+    // was asked to exit with
+    pub last_exit: ProcessExit,
+    pub exit: Option<i32>,
+    // whether anything has set last_exit since the line started
+    pub exit_set: bool,
+    // name of the last command run, for error messages
+    pub last_command: String,
+    // the last error that reached the top level
+    pub last_error: Option<WashError>,
     pub catch_sigint: bool
 }
 
@@ -106,20 +131,30 @@ impl fmt::Debug for ASTRunner {
 }
 
 impl ASTRunner {
-    pub fn new(sections:SectionTable) -> ASTRunner {
+    pub fn new(name:&String, sections:SectionTable) -> ASTRunner {
         ASTRunner {
             sections: sections,
-            position: SectionType::Run
+            position: SectionType::Run,
+            name: name.clone(),
+            pos: None
         }
     }
 
-    pub fn evaluate(&mut self, args:&WashArgs, env:&mut WashEnv) -> Result<WashArgs, String> {
+    pub fn evaluate(&mut self, args:&WashArgs, env:&mut WashEnv) -> WashResult<WashArgs> {
+        self.pos = None;
+        match self.run(args, env) {
+            Err(e) => Err(e.traced(&self.name, self.pos.clone())),
+            Ok(v) => Ok(v)
+        }
+    }
+
+    fn run(&mut self, args:&WashArgs, env:&mut WashEnv) -> WashResult<WashArgs> {
         self.position = SectionType::Run;
         let mut cfv = WashArgs::Empty;
         let mut vs = LinkedList::new();
         loop {
             let section = match self.sections.get(&self.position) {
-                None => return Err(format!("Reached unknown section")),
+                None => return Err(WashError::runtime(format!("Reached unknown section"))),
                 Some(sec) => sec.clone()
             };
             let mut iter = section.into_iter();
@@ -127,6 +162,9 @@ impl ASTRunner {
                 match iter.next() {
                     None => return Ok(cfv),
                     Some(action) => match action {
+                        Mark(p) => {
+                            self.pos = Some(p);
+                        },
                         Jump(n) => {
                             self.position = SectionType::Number(n);
                             break;
@@ -177,14 +215,14 @@ impl ASTRunner {
                             let top = match vs.back() {
                                 None => vec![],
                                 Some(&WashArgs::Long(ref v)) => v.clone(),
-                                Some(_) => return Err(format!("Did not find a list"))
+                                Some(_) => return Err(WashError::runtime(format!("Did not find a list")))
                             };
                             let index:usize = match from_str_radix(cfv.get_flat(0).as_slice(), 10) {
-                                Err(_) => return Err(format!("Could not turn \"{}\" into a number", cfv.get_flat(0))),
+                                Err(_) => return Err(WashError::runtime(format!("Could not turn \"{}\" into a number", cfv.get_flat(0)))),
                                 Ok(v) => v
                             };
                             if index > top.len() {
-                                return Err(format!("{} outside list range", index));
+                                return Err(WashError::runtime(format!("{} outside list range", index)));
                             } else {
                                 cfv = top[index].clone();
                             }
@@ -240,7 +278,10 @@ impl ASTRunner {
                             vs.push_back(try!(env.runf(&n, &args)));
                         },
                         Fail(m) => {
-                            return Err(m);
+                            return Err(WashError::runtime(m));
+                        },
+                        Halt => {
+                            return Err(WashError::stop());
                         },
                         DStore(n, p) => {
                             if p.is_empty() {
@@ -271,14 +312,14 @@ impl ASTRunner {
                         },
                         Store => {
                             let com_name = match vs.pop_back() {
-                                None => return Err(format!("No variable name found")),
+                                None => return Err(WashError::runtime(format!("No variable name found"))),
                                 Some(WashArgs::Flat(s)) => s,
-                                Some(_) => return Err(format!("Variable names must be flat"))
+                                Some(_) => return Err(WashError::runtime(format!("Variable names must be flat")))
                             };
                             match VAR_PATH_REGEX.captures(com_name.as_slice()) {
                                 None => match VAR_REGEX.captures(com_name.as_slice()) {
-                                    None => return Err(format!("Variable name {} could not be resolved into $path:name",
-                                                               com_name)),
+                                    None => return Err(WashError::runtime(format!("Variable name {} could not be resolved into $path:name",
+                                                               com_name))),
                                     Some(caps) => {
                                         let name = caps.at(1).unwrap();
                                         try!(env.insv(name.to_string(), cfv));
@@ -296,12 +337,12 @@ impl ASTRunner {
                         Load => {
                             let com_name = match cfv {
                                 WashArgs::Flat(s) => s,
-                                _ => return Err(format!("Variable names must be flat"))
+                                _ => return Err(WashError::runtime(format!("Variable names must be flat")))
                             };
                             match VAR_PATH_REGEX.captures(com_name.as_slice()) {
                                 None => match VAR_REGEX.captures(com_name.as_slice()) {
-                                    None => return Err(format!("Variable name {} could not be resolved into $path:name",
-                                                               com_name)),
+                                    None => return Err(WashError::runtime(format!("Variable name {} could not be resolved into $path:name",
+                                                               com_name))),
                                     Some(caps) => {
                                         let name = caps.at(1).unwrap();
                                         cfv = try!(env.getv(&name.to_string()));
@@ -317,7 +358,7 @@ impl ASTRunner {
                         Save(s) => {
                             let name = cfv.get_flat(0);
                             if name.is_empty() {
-                                return Err(format!("Function names cannot be empty"));
+                                return Err(WashError::runtime(format!("Function names cannot be empty")));
                             }
                             // TODO: this is not very efficient, it just copies all the sections
                             // we have into the function's section, replacing .run with the given
@@ -326,7 +367,7 @@ impl ASTRunner {
                             // functions and including just those sections.
                             let mut sections = self.sections.clone();
                             let runsec = match sections.remove(&SectionType::Number(s)) {
-                                None => return Err(format!("Function section not found")),
+                                None => return Err(WashError::runtime(format!("Function section not found"))),
                                 Some(s) => s
                             };
                             sections.insert(SectionType::Run, runsec);
@@ -349,6 +390,15 @@ impl WashEnv {
             functions: HashMap::new(),
             scripts: HashMap::new(),
             term: TermState::new(),
+            settings: Settings::new(),
+            keymap: Keymap::new(),
+            edit_mode: format!("emacs"),
+            args: env::args().collect(),
+            last_exit: ExitStatus(0),
+            exit: None,
+            exit_set: false,
+            last_command: String::new(),
+            last_error: None,
             catch_sigint: true
         }
     }
@@ -385,28 +435,28 @@ impl WashEnv {
         self.term.controls.flush();
     }
 
-    pub fn restart_job(&mut self, id:&usize) -> Result<(), String> {
+    pub fn restart_job(&mut self, id:&usize) -> WashResult<()> {
         self.term.restart_job(id)
     }
 
-    pub fn front_job(&mut self) -> Result<usize, String> {
+    pub fn front_job(&mut self) -> WashResult<usize> {
         match self.term.front_job() {
-            None => return Err(format!("No front job")),
+            None => return Err(WashError::runtime(format!("No front job"))),
             Some(u) => Ok(u)
         }
     }
     
     pub fn run_job_fd(&mut self, stdin:Option<Fd>, stdout:Option<Fd>, stderr:Option<Fd>,
                       name:&String, args:&Vec<String>,
-                      envs:&Vec<(String, Option<String>)>) -> Result<usize, String> {
+                      envs:&Vec<(String, Option<String>)>) -> WashResult<usize> {
         self.term.run_job_fd(stdin, stdout, stderr, name, args, envs)
     }
 
-    pub fn run_job(&mut self, name:&String, args:&Vec<String>) -> Result<usize, String> {
+    pub fn run_job(&mut self, name:&String, args:&Vec<String>) -> WashResult<usize> {
         self.term.run_job(name, args)
     }
 
-    pub fn get_job(&self, id:&usize) -> Result<&Job, String> {
+    pub fn get_job(&self, id:&usize) -> WashResult<&Job> {
         self.term.get_job(id)
     }
 
@@ -414,28 +464,49 @@ impl WashEnv {
         self.term.jobs.contains_key(id)
     }
 
-    pub fn job_output(&mut self, id:&usize) -> Result<ProcessOutput, String> {
+    pub fn job_output(&mut self, id:&usize) -> WashResult<ProcessOutput> {
         self.term.job_output(id)
     }
     
     pub fn run_command_fd(&mut self, stdin:Option<Fd>, stdout:Option<Fd>, stderr:Option<Fd>,
                           name:&String, args:&Vec<String>,
-                          envs:&Vec<(String, Option<String>)>) -> Result<ProcessExit, String> {
+                          envs:&Vec<(String, Option<String>)>) -> WashResult<ProcessExit> {
         self.term.run_command_fd(stdin, stdout, stderr, name, args, envs)
     }
 
-    pub fn run_command(&mut self, name:&String, args:&Vec<String>) -> Result<ProcessExit, String> {
+    pub fn run_command(&mut self, name:&String, args:&Vec<String>) -> WashResult<ProcessExit> {
         self.term.run_command(name, args)
     }
 
-    pub fn wait_job(&mut self, id:&usize) -> Result<ProcessExit, String> {
+    pub fn wait_job(&mut self, id:&usize) -> WashResult<ProcessExit> {
         self.term.wait_job(id)
     }
 
-    pub fn remove_if_done(&mut self, id:&usize) -> Result<bool, String> {
+    pub fn remove_if_done(&mut self, id:&usize) -> WashResult<bool> {
         self.term.remove_if_done(id)
     }
 
+    pub fn set_exit(&mut self, exit:ProcessExit) {
+        self.last_exit = exit;
+        self.exit_set = true;
+    }
+
+    pub fn exit_status(&self) -> i32 {
+        match self.last_exit {
+            ExitStatus(status) => status as i32,
+            // same as bash
+            ExitSignal(sig) => 128 + sig as i32
+        }
+    }
+
+    // status wash itself should exit with
+    pub fn final_status(&self) -> i32 {
+        match self.exit {
+            Some(status) => status,
+            None => self.exit_status()
+        }
+    }
+
     pub fn hasv(&self, name:&String) -> bool {
         self.hasvp(name, &self.variables)
     }
@@ -455,7 +526,7 @@ impl WashEnv {
         self.paths.contains_key(path)
     }
 
-    pub fn insv(&mut self, name:String, val:WashArgs) -> Result<WashArgs, String> {
+    pub fn insv(&mut self, name:String, val:WashArgs) -> WashResult<WashArgs> {
         let path = self.variables.clone();
         if !self.hasp(&path) {
             try!(self.insp(path.clone()));
@@ -463,13 +534,16 @@ impl WashEnv {
         return self.insvp(name, path, val);
     }
 
-    pub fn insvp(&mut self, name:String, path:String, val:WashArgs) -> Result<WashArgs, String> {
+    pub fn insvp(&mut self, name:String, path:String, val:WashArgs) -> WashResult<WashArgs> {
         if val.is_empty() {
             // unset
             if path == "sys" {
-                return Err(format!("System variables are read-only"));
+                return Err(WashError::runtime(format!("System variables are read-only")));
             } else if path == "pipe" {
-                return Err("Pipes are read-only variables".to_string())
+                return Err(WashError::runtime("Pipes are read-only variables".to_string()))
+            } else if path == "cfg" {
+                // back to the default
+                return self.settings.reset(&name);
             } else if path == "env" {
                 env::remove_var(name.as_slice());
                 return Ok(Empty);
@@ -484,12 +558,14 @@ impl WashEnv {
             }
         } else {
             if path == "pipe" {
-                return Err(format!("Pipes are read-only variales"));
+                return Err(WashError::runtime(format!("Pipes are read-only variales")));
             } else if path == "sys" {
-                return Err(format!("System variables are read-only"))
+                return Err(WashError::runtime(format!("System variables are read-only")))
+            } else if path == "cfg" {
+                return self.settings.set(&name, &val);
             } else if path == "env" {
                 if !val.is_flat() {
-                    return Err("Environment variables can only be flat".to_string());
+                    return Err(WashError::runtime("Environment variables can only be flat".to_string()));
                 }
                 env::set_var(name.as_slice(), val.flatten().as_slice());
                 return Ok(val);
@@ -503,28 +579,32 @@ impl WashEnv {
         }
     }
 
-    pub fn insp(&mut self, path:String) -> Result<WashArgs, String> {
+    pub fn insp(&mut self, path:String) -> WashResult<WashArgs> {
         self.paths.insert(path, HashMap::new());
         return Ok(Empty);
     }
 
-    pub fn insf(&mut self, name:String, func:FuncEntry) -> Result<WashArgs, String> {
+    pub fn insf(&mut self, name:String, func:FuncEntry) -> WashResult<WashArgs> {
         self.functions.insert(name, func);
         return Ok(Empty);
     }
 
-    pub fn insfd(&mut self, name:&str, func:WashFunc) -> Result<WashArgs, String> {
+    pub fn insfd(&mut self, name:&str, func:WashFunc) -> WashResult<WashArgs> {
         self.insf(name.to_string(), Direct(func))
     }
 
-    pub fn getv(&self, name:&String) -> Result<WashArgs, String> {
+    pub fn insfc(&mut self, name:&str, func:WashFunc) -> WashResult<WashArgs> {
+        self.insf(name.to_string(), Command(func))
+    }
+
+    pub fn getv(&self, name:&String) -> WashResult<WashArgs> {
         return match self.getvp(name, &self.variables) {
             Err(_) => return self.getvp(name, &"".to_string()),
             v => v
         };
     }
 
-    pub fn getall(&self) -> Result<WashArgs, String> {
+    pub fn getall(&self) -> WashResult<WashArgs> {
         let mut out = match self.getallp(&self.variables) {
             Ok(Long(v)) => v,
             _ => vec![]
@@ -542,9 +622,11 @@ impl WashEnv {
         return Ok(Long(out));
     }
     
-    pub fn getallp(&self, path:&String) -> Result<WashArgs, String> {
+    pub fn getallp(&self, path:&String) -> WashResult<WashArgs> {
         if *path == "sys" {
-            return Err(format!("Cannot get all system variables"));
+            return Err(WashError::runtime(format!("Cannot get all system variables")));
+        } else if *path == "cfg" {
+            return Ok(self.settings.all_args());
         } else if *path == "env" {
             let mut out = vec![];
             let envs = env::vars();
@@ -580,11 +662,11 @@ impl WashEnv {
             }
             return Ok(Long(out));
         } else {
-            return Err("Path not found".to_string());
+            return Err(WashError::runtime("Path not found".to_string()));
         }
     }
 
-    pub fn getvp(&self, name:&String, path:&String) -> Result<WashArgs, String> {
+    pub fn getvp(&self, name:&String, path:&String) -> WashResult<WashArgs> {
         if *path == "sys" {
             // special variables like usernames and things
             if *name == "login" {
@@ -595,10 +677,24 @@ impl WashEnv {
                          "Could not get hostname: {err}")));
             } else if *name == "args" {
                 let mut out = vec![];
-                for arg in env::args() {
-                    out.push(Flat(arg));
+                for arg in self.args.iter() {
+                    out.push(Flat(arg.clone()));
                 }
                 return Ok(Long(out));
+            } else if *name == "status" {
+                return Ok(Flat(format!("{}", self.exit_status())));
+            } else if *name == "error" {
+                return match self.last_error {
+                    None => Ok(Empty),
+                    Some(ref e) => Ok(e.as_args())
+                };
+            } else if *name == "signal" {
+                return match self.last_exit {
+                    ExitSignal(sig) => Ok(Flat(format!("{}", sig))),
+                    ExitStatus(_) => Ok(Empty)
+                };
+            } else if *name == "mode" {
+                return Ok(Flat(self.edit_mode.clone()));
             } else if *name == "cwd" {
                 let cwd = tryf!(env::current_dir(),
                                 "Couldn't get current directory: {err}");
@@ -608,29 +704,31 @@ impl WashEnv {
                                 "Couldn't get current directory: {err}");
                 return Ok(Flat(format!("{}", condense_path(cwd.as_path().to_path_buf()).display())));
             } else {
-                return Err(format!("System variable not found"));
+                return Err(WashError::runtime(format!("System variable not found")));
             }
+        } else if *path == "cfg" {
+            return self.settings.get_args(name);
         } else if *path == "env" {
             // environment variables
             return match env::var(name.as_slice()) {
-                Err(e) => Err(format!("{}", e)),
+                Err(e) => Err(WashError::runtime(format!("{}", e))),
                 Ok(s) => Ok(Flat(s))
             }
         } else if *path == "pipe" {
             // pipe Fd's
             let from = try!(self.get_job(&match from_str_radix(name.as_slice(), 10) {
-                Err(e) => return Err(format!("Did not give job number: {}", e)),
+                Err(e) => return Err(WashError::runtime(format!("Did not give job number: {}", e))),
                 Ok(v) => v
             }));
             match from.process.stdout {
-                None => return Err("Job has no output handles".to_string()),
+                None => return Err(WashError::runtime("Job has no output handles".to_string())),
                 Some(ref p) => Ok(Flat(format!("@{}", p.as_raw_fd())))
             }
         } else {
             return match self.paths.get(path) {
-                None => Err("Path not found".to_string()),
+                None => Err(WashError::runtime("Path not found".to_string())),
                 Some(table) => match table.get(name) {
-                    None => Err("Variable not found".to_string()),
+                    None => Err(WashError::runtime("Variable not found".to_string())),
                     Some(val) => Ok(val.clone())
                 }
             };
@@ -691,25 +789,32 @@ impl WashEnv {
         }
     }
 
-    pub fn func_stop(&self) -> Result<(), String> {
+    pub fn func_stop(&self) -> WashResult<()> {
         unsafe {
             if uexec_stop {
-                return Err("Interrupt".to_string());
+                return Err(WashError::interrupt());
             } else {
                 return Ok(());
             }
         }
     }
 
-    pub fn runf(&mut self, name:&String, args:&WashArgs) -> Result<WashArgs, String> {
+    pub fn runf(&mut self, name:&String, args:&WashArgs) -> WashResult<WashArgs> {
         let mut func = None; let mut runner = None;
+        let command;
         match self.functions.get(name) {
-            None => return Err("Function not found".to_string()),
+            None => return Err(WashError::runtime(format!("Function not found: {}", name))),
             Some(&Direct(ref f)) => {
                 func = Some(f.clone());
+                command = false;
+            },
+            Some(&Command(ref f)) => {
+                func = Some(f.clone());
+                command = true;
             },
             Some(&Indirect(ref sections)) => {
-                runner = Some(ASTRunner::new(sections.clone()));
+                runner = Some(ASTRunner::new(name, sections.clone()));
+                command = false;
             }
         };
         self.handle_sigint();
@@ -721,6 +826,7 @@ impl WashEnv {
         } else {
             do_unhandle = false;
         }
+        let indirect = runner.is_some();
         let out = {
             if func.is_some() {
                 func.unwrap()(args, self)
@@ -734,16 +840,25 @@ impl WashEnv {
             self.catch_sigint = true;
             self.unhandle_sigint();
         }
+        // functions keep the status of what ran in them, and other
+        // builtins leave it alone
+        match out {
+            Ok(_) if command => self.set_exit(ExitStatus(0)),
+            Err(ref e) if !e.is_stop() && (command || (indirect && self.exit_status() == 0)) =>
+                self.set_exit(ExitStatus(1)),
+            _ => {}
+        }
         return out;
     }
 
-    pub fn load_script(&mut self, path:PathBuf, args:&WashArgs) -> Result<WashArgs, String> {
+    pub fn load_script(&mut self, path:PathBuf, args:&WashArgs) -> WashResult<WashArgs> {
         let mut script = match self.scripts.remove(&path) {
             Some(script) => script,
             None => WashScript::new(path.as_path())
         };
-        if !script.is_compiled() && !try!(script.compile()) {
-            return Err("Failed to compile script".to_string());
+        let cache = expand_path(PathBuf::new(self.settings.text("wo_path").as_slice()));
+        if !script.is_compiled() && !try!(script.compile(cache.as_path())) {
+            return Err(WashError::compile("Failed to compile script".to_string()));
         }
         self.term.controls.flush();
         if script.is_runnable() {
@@ -755,13 +870,13 @@ impl WashEnv {
             self.scripts.insert(path.clone(), script);
             return out;
         } else {
-            return Err("Cannot load or run script".to_string());
+            return Err(WashError::runtime("Cannot load or run script".to_string()));
         }
     }
 
-    fn run_script(&mut self, args:&WashArgs, script:&mut WashScript) -> Result<WashArgs, String> {
+    fn run_script(&mut self, args:&WashArgs, script:&mut WashScript) -> WashResult<WashArgs> {
         if !script.is_compiled() {
-            return Err("String is not compiled".to_string());
+            return Err(WashError::runtime("String is not compiled".to_string()));
         }
         
         let run_func:WashRun = unsafe {mem::transmute(try!(script.get_run()))};
@@ -773,15 +888,15 @@ impl WashEnv {
         return run_func(args, self);
     }
 
-    fn load_script_inner(&mut self, args:&WashArgs, script:&mut WashScript) -> Result<WashArgs, String> {
+    fn load_script_inner(&mut self, args:&WashArgs, script:&mut WashScript) -> WashResult<WashArgs> {
         if !script.is_compiled() {
-            return Err("Script is not compiled".to_string());
+            return Err(WashError::runtime("Script is not compiled".to_string()));
         }
 
         let load_func:WashLoad = unsafe {mem::transmute(try!(script.get_load()))};
 
         if script.loaded {
-            return Err("Script is already loaded".to_string());
+            return Err(WashError::runtime("Script is already loaded".to_string()));
         }
 
         let out = load_func(args, self);
@@ -789,14 +904,18 @@ impl WashEnv {
         return out;
     }
 
-    pub fn output_file(&mut self, path:&Path) -> Result<Fd, String> {
-        Ok(tryf!(self.term.output_file(path),
-                 "Couldn't open file: {err}"))
+    pub fn output_file(&mut self, path:&Path) -> WashResult<Fd> {
+        match self.term.output_file(path) {
+            Err(e) => Err(WashError::io(format!("Couldn't open file: {}", e))),
+            Ok(fd) => Ok(fd)
+        }
     }
 
-    pub fn input_file(&mut self, path:&Path) -> Result<Fd, String> {
-        Ok(tryf!(self.term.input_file(path),
-                 "Couldn't open file: {err}"))
+    pub fn input_file(&mut self, path:&Path) -> WashResult<Fd> {
+        match self.term.input_file(path) {
+            Err(e) => Err(WashError::io(format!("Couldn't open file: {}", e))),
+            Ok(fd) => Ok(fd)
+        }
     }
 
     pub fn get_jobs(&mut self) -> WashArgs {
@@ -842,3 +961,18 @@ impl WashEnv {
     }
 
 }
+
+#[test]
+fn prompt_keeps_status_test() {
+    let mut env = WashEnv::new();
+    ::builtins::load_builtins(&mut env).unwrap();
+    let run = Long(vec![Flat("false".to_string())]);
+    assert!(env.runf(&"run".to_string(), &run).is_ok());
+    assert_eq!(env.exit_status(), 1);
+    // plain builtins like prompt leave the status of the command before
+    assert!(env.runf(&"prompt".to_string(), &Empty).is_ok());
+    assert_eq!(env.exit_status(), 1);
+    // and builtins used like commands set it
+    assert!(env.runf(&"cd".to_string(), &Flat(".".to_string())).is_ok());
+    assert_eq!(env.exit_status(), 0);
+}
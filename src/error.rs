@@ -0,0 +1,205 @@
+use std::error::FromError;
+use std::fmt;
+use std::io;
+
+use types::*;
+
+use self::ErrorKind::*;
+
+pub type WashResult<T> = Result<T, WashError>;
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum ErrorKind {
+    // input couldn't be turned into actions
+    Parse,
+    // actions couldn't be optimized, or a script couldn't be compiled
+    Compile,
+    // errors while running actions and functions
+    Runtime,
+    // a command or job couldn't be started
+    Spawn,
+    Io,
+    // C-c while running a function
+    Interrupt,
+    // the silent error, stops evaluation without a message
+    Stop
+}
+
+// one function the error passed through on its way out,
+// with the line that function was running
+#[derive(Clone)]
+pub struct Frame {
+    pub func: String,
+    pub pos: Option<SourcePos>
+}
+
+#[derive(Clone)]
+pub struct WashError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub cause: Option<Box<WashError>>,
+    pub location: Option<SourcePos>,
+    // inner-most frame first
+    pub trace: Vec<Frame>
+}
+
+impl WashError {
+    pub fn new(kind:ErrorKind, message:String) -> WashError {
+        WashError {
+            kind: kind,
+            message: message,
+            cause: None,
+            location: None,
+            trace: vec![]
+        }
+    }
+
+    pub fn parse(message:String) -> WashError {
+        WashError::new(Parse, message)
+    }
+
+    pub fn compile(message:String) -> WashError {
+        WashError::new(Compile, message)
+    }
+
+    pub fn runtime(message:String) -> WashError {
+        WashError::new(Runtime, message)
+    }
+
+    pub fn spawn(message:String) -> WashError {
+        WashError::new(Spawn, message)
+    }
+
+    pub fn io(message:String) -> WashError {
+        WashError::new(Io, message)
+    }
+
+    pub fn interrupt() -> WashError {
+        WashError::new(Interrupt, format!("Interrupt"))
+    }
+
+    pub fn stop() -> WashError {
+        WashError::new(Stop, format!("stop"))
+    }
+
+    pub fn caused_by(mut self, cause:WashError) -> WashError {
+        self.cause = Some(box cause);
+        return self;
+    }
+
+    pub fn at(mut self, pos:SourcePos) -> WashError {
+        // keep the inner-most location
+        if self.location.is_none() {
+            self.location = Some(pos);
+        }
+        return self;
+    }
+
+    // called as the error leaves an ASTRunner
+    pub fn traced(mut self, func:&String, pos:Option<SourcePos>) -> WashError {
+        if self.is_stop() {
+            // nobody sees these
+            return self;
+        }
+        match pos {
+            None => {},
+            Some(ref p) => {
+                self = self.at(p.clone());
+            }
+        }
+        self.trace.push(Frame {
+            func: func.clone(),
+            pos: pos
+        });
+        return self;
+    }
+
+    // lines describing the functions the error went through,
+    // empty if it didn't happen inside a function
+    pub fn backtrace(&self) -> Vec<String> {
+        let mut out = vec![];
+        for frame in self.trace.iter() {
+            if frame.func.is_empty() {
+                // top-level code
+                continue;
+            }
+            out.push(match frame.pos {
+                None => format!("in {}", frame.func),
+                Some(ref pos) => format!("in {} ({})", frame.func, pos)
+            });
+        }
+        return out;
+    }
+
+    pub fn is_stop(&self) -> bool {
+        self.kind == Stop
+    }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind {
+            Parse => "parse",
+            Compile => "compile",
+            Runtime => "runtime",
+            Spawn => "spawn",
+            Io => "io",
+            Interrupt => "interrupt",
+            Stop => "stop"
+        }
+    }
+
+    // wash representation, used for $sys:error
+    pub fn as_args(&self) -> WashArgs {
+        let mut out = vec![WashArgs::Flat(self.kind_name().to_string()),
+                           WashArgs::Flat(format!("{}", self))];
+        match self.location {
+            None => {},
+            Some(ref pos) => out.push(WashArgs::Flat(format!("{}", pos)))
+        }
+        WashArgs::Long(out)
+    }
+}
+
+impl fmt::Display for WashError {
+    fn fmt(&self, fmt:&mut fmt::Formatter) -> fmt::Result {
+        try!(fmt.write_str(self.message.as_slice()));
+        match self.cause {
+            None => {},
+            Some(ref cause) => {
+                try!(fmt.write_fmt(format_args!(": {}", cause)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for WashError {
+    fn fmt(&self, fmt:&mut fmt::Formatter) -> fmt::Result {
+        try!(fmt.write_fmt(format_args!("WashError({}, {})", self.kind_name(), self.message)));
+        match self.location {
+            None => {},
+            Some(ref pos) => {
+                try!(fmt.write_fmt(format_args!(" at {}", pos)));
+            }
+        }
+        match self.cause {
+            None => {},
+            Some(ref cause) => {
+                try!(fmt.write_fmt(format_args!(" caused by {:?}", cause)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromError<io::Error> for WashError {
+    fn from_error(err:io::Error) -> WashError {
+        WashError::io(format!("{}", err))
+    }
+}
+
+// lets tryf! and plain messages be used as runtime errors
+impl FromError<String> for WashError {
+    fn from_error(err:String) -> WashError {
+        WashError::runtime(err)
+    }
+}
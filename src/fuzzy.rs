@@ -0,0 +1,262 @@
+// Fuzzy matching in the style of flx: every character of the pattern
+// has to appear in the text in order, but not always next to each other.
+// Matches are scored so the ones that were probably meant come first
+use std::ascii::AsciiExt;
+use std::cmp::{min, Ordering};
+use std::collections::*;
+
+// points for every matched character
+const MATCH_SCORE:isize = 1;
+// matched character starts a word
+const BOUNDARY_BONUS:isize = 8;
+// matched character comes right after the last one
+const CONSECUTIVE_BONUS:isize = 6;
+// most taken off for characters skipped before the first match
+const MAX_LEADING_PENALTY:isize = 3;
+// given to the newest text in a ranking, less to older ones
+const RECENT_BONUS:isize = 4;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct FuzzyMatch {
+    pub score: isize,
+    // indices of the matched characters in the text, in chars
+    pub positions: Vec<usize>
+}
+
+fn is_separator(ch:char) -> bool {
+    match ch {
+        ' ' | '/' | '_' | '-' | '.' | ':' | ',' | '$' | '(' | ')' | '"' => true,
+        _ => false
+    }
+}
+
+fn is_boundary(text:&Vec<char>, i:usize) -> bool {
+    if i == 0 {
+        return true;
+    }
+    let prev = text[i - 1];
+    return is_separator(prev) || (prev.is_lowercase() && text[i].is_uppercase());
+}
+
+// best way of matching pattern against text, if there is one
+pub fn fuzzy_match(pattern:&str, text:&str) -> Option<FuzzyMatch> {
+    let pattern:Vec<char> = pattern.chars().collect();
+    let text:Vec<char> = text.chars().collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch {
+            score: 0,
+            positions: vec![]
+        });
+    }
+    if pattern.len() > text.len() {
+        return None;
+    }
+    // patterns without capitals match either case
+    let fold = !pattern.iter().any(|c| c.is_uppercase());
+    let same = |a:char, b:char| {
+        if fold {
+            a.to_ascii_lowercase() == b.to_ascii_lowercase()
+        } else {
+            a == b
+        }
+    };
+    let n = text.len();
+    // best[i][j] is the best score with pattern[i] matched at text[j],
+    // and back[i][j] is where pattern[i - 1] was matched to get it
+    let mut best:Vec<Vec<Option<isize>>> = vec![];
+    let mut back:Vec<Vec<usize>> = vec![];
+    for i in 0..pattern.len() {
+        let mut row = vec![None; n];
+        let mut from = vec![0; n];
+        // best score in the last row before j - 1, and where it was
+        let mut run:Option<(isize, usize)> = None;
+        for j in 0..n {
+            if i > 0 && j >= 2 {
+                match best[i - 1][j - 2] {
+                    Some(s) if run.map_or(true, |(r, _)| s > r) => run = Some((s, j - 2)),
+                    _ => {}
+                }
+            }
+            if !same(pattern[i], text[j]) {
+                continue;
+            }
+            let mut bonus = MATCH_SCORE;
+            if is_boundary(&text, j) {
+                bonus += BOUNDARY_BONUS;
+            }
+            if i == 0 {
+                row[j] = Some(bonus - min(j as isize, MAX_LEADING_PENALTY));
+                continue;
+            }
+            match run {
+                None => {},
+                Some((s, k)) => {
+                    row[j] = Some(s + bonus);
+                    from[j] = k;
+                }
+            }
+            if j >= 1 {
+                match best[i - 1][j - 1] {
+                    Some(s) if row[j].map_or(true, |r| s + bonus + CONSECUTIVE_BONUS >= r) => {
+                        row[j] = Some(s + bonus + CONSECUTIVE_BONUS);
+                        from[j] = j - 1;
+                    },
+                    _ => {}
+                }
+            }
+        }
+        best.push(row);
+        back.push(from);
+    }
+    let last = pattern.len() - 1;
+    let mut end = None;
+    for j in 0..n {
+        match best[last][j] {
+            Some(s) if end.map_or(true, |(e, _)| s > e) => end = Some((s, j)),
+            _ => {}
+        }
+    }
+    let (score, mut j) = match end {
+        None => return None,
+        Some(e) => e
+    };
+    let mut positions = vec![j];
+    let mut i = last;
+    while i > 0 {
+        j = back[i][j];
+        positions.push(j);
+        i -= 1;
+    }
+    positions.reverse();
+    return Some(FuzzyMatch {
+        score: score,
+        positions: positions
+    });
+}
+
+// texts pattern matches, best first, along with their index in texts.
+// If recent is set texts are taken to be newest first, and newer ones
+// get a little more
+pub fn fuzzy_rank(pattern:&str, texts:&Vec<String>, recent:bool) -> Vec<(usize, FuzzyMatch)> {
+    let mut out = vec![];
+    for (i, text) in texts.iter().enumerate() {
+        match fuzzy_match(pattern, text.as_slice()) {
+            None => {},
+            Some(mut m) => {
+                if recent {
+                    m.score += RECENT_BONUS * ((texts.len() - i) as isize) / (texts.len() as isize);
+                }
+                out.push((i, m));
+            }
+        }
+    }
+    out.sort_by(|&(ai, ref a), &(bi, ref b)| {
+        match b.score.cmp(&a.score) {
+            // shorter texts, then earlier ones, win ties
+            Ordering::Equal => match texts[ai].len().cmp(&texts[bi].len()) {
+                Ordering::Equal => ai.cmp(&bi),
+                o => o
+            },
+            o => o
+        }
+    });
+    return out;
+}
+
+// pattern as it is somewhere in text, with case folded the same way
+fn substring_match(pattern:&str, text:&str) -> Option<FuzzyMatch> {
+    let fold = !pattern.chars().any(|c| c.is_uppercase());
+    let lower = |s:&str| -> String {
+        if fold {s.chars().map(|c| c.to_ascii_lowercase()).collect()} else {s.to_string()}
+    };
+    let (pattern, text) = (lower(pattern), lower(text));
+    let start = match text.find(pattern.as_slice()) {
+        None => return None,
+        Some(i) => text[..i].chars().count()
+    };
+    Some(FuzzyMatch {
+        score: 0,
+        positions: range(start, start + pattern.chars().count()).collect()
+    })
+}
+
+// for searching history, where texts are newest first: lines with the
+// pattern in them as typed come first and newest first, so searching
+// again goes back in time, then the rest ranked as fuzzy_rank does.
+// Each line is only given once
+pub fn history_rank(pattern:&str, texts:&Vec<String>) -> Vec<(usize, FuzzyMatch)> {
+    let mut seen = HashSet::new();
+    let mut out = vec![];
+    let mut rest = vec![];
+    for (i, text) in texts.iter().enumerate() {
+        if !seen.insert(text.as_slice()) {
+            continue;
+        }
+        match substring_match(pattern, text.as_slice()) {
+            None => rest.push(i),
+            Some(m) => out.push((i, m))
+        }
+    }
+    let rest_texts:Vec<String> = rest.iter().map(|i| texts[*i].clone()).collect();
+    for (j, m) in fuzzy_rank(pattern, &rest_texts, true).into_iter() {
+        out.push((rest[j], m));
+    }
+    return out;
+}
+
+#[cfg(test)]
+fn ranked(pattern:&str, texts:Vec<&str>, recent:bool) -> Vec<String> {
+    let texts:Vec<String> = texts.iter().map(|s| s.to_string()).collect();
+    fuzzy_rank(pattern, &texts, recent).iter().map(|&(i, _)| texts[i].clone()).collect()
+}
+
+#[test]
+fn fuzzy_match_test() {
+    assert_eq!(fuzzy_match("abc", "a_b_c").unwrap().positions, vec![0, 2, 4]);
+    assert_eq!(fuzzy_match("cba", "a_b_c"), None);
+    assert_eq!(fuzzy_match("abcd", "abc"), None);
+    // lower case matches either, capitals only themselves
+    assert!(fuzzy_match("ma", "Main").is_some());
+    assert_eq!(fuzzy_match("Ma", "main"), None);
+    // the boundary is preferred to the first b
+    assert_eq!(fuzzy_match("fb", "fabric_bar").unwrap().positions, vec![0, 7]);
+}
+
+#[test]
+fn fuzzy_rank_test() {
+    // word starts beat letters in the middle of words
+    assert_eq!(ranked("fb", vec!["fabric", "foo_bar"], false),
+               vec!["foo_bar".to_string(), "fabric".to_string()]);
+    // runs beat scattered letters
+    assert_eq!(ranked("abc", vec!["xaxbxc", "xabcx"], false),
+               vec!["xabcx".to_string(), "xaxbxc".to_string()]);
+    // shorter texts win ties
+    assert_eq!(ranked("src", vec!["src/main.rs", "src"], false),
+               vec!["src".to_string(), "src/main.rs".to_string()]);
+    // texts that don't match are left out
+    assert_eq!(ranked("zz", vec!["src", "ls"], false), Vec::<String>::new());
+}
+
+#[test]
+fn fuzzy_rank_recent_test() {
+    // newer texts win otherwise equal matches
+    assert_eq!(ranked("ls", vec!["ls -l", "ls -a"], true),
+               vec!["ls -l".to_string(), "ls -a".to_string()]);
+    // but not better ones
+    assert_eq!(ranked("ls", vec!["less", "ls"], true),
+               vec!["ls".to_string(), "less".to_string()]);
+}
+
+#[test]
+fn history_rank_test() {
+    let texts:Vec<String> = vec!["git push", "make", "git pull", "git push", "gpsh"]
+        .iter().map(|s| s.to_string()).collect();
+    let ranked:Vec<(usize, Vec<usize>)> = history_rank("pu", &texts).into_iter()
+        .map(|(i, m)| (i, m.positions)).collect();
+    // newest first whatever the score, the older git push is left out,
+    // and lines only matching loosely come last
+    assert_eq!(ranked, vec![(0, vec![4, 5]), (2, vec![4, 5])]);
+    let ranked:Vec<usize> = history_rank("gps", &texts).into_iter().map(|(i, _)| i).collect();
+    assert_eq!(ranked, vec![4, 0]);
+    assert_eq!(history_rank("Make", &texts).len(), 0);
+}
@@ -0,0 +1,251 @@
+// Completion as a graph, like the thoughts on completion in the TODO:
+// nodes are commands, arguments, directories and history lines, with
+// edges between those that came up together. Completions are weight
+// functions over the edges out of a node, and pick either the lowest
+// (min-complete) or highest (max-complete) weighted neighbors
+use std::collections::*;
+use std::cmp::Ordering;
+
+use history::*;
+use completion::*;
+
+use self::NodeKind::*;
+use self::EdgeKind::*;
+use self::Completion::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum NodeKind {
+    Command,
+    Argument,
+    Directory,
+    History
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EdgeKind {
+    // command to an argument that followed it
+    Follows,
+    // directory to a command or line run in it
+    RunIn,
+    // command to a line that started with it
+    Started
+}
+
+pub struct Node {
+    pub kind: NodeKind,
+    pub text: String,
+    // times it's been used, and when it was last, in milliseconds
+    pub count: usize,
+    pub last: u64,
+    // exit status the last time, for history lines
+    pub status: i32
+}
+
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub to: usize,
+    pub count: usize,
+    pub last: u64
+}
+
+// weight of an edge to a node, given the time now
+pub type WeightFn = fn(&Node, &Edge, u64) -> u64;
+
+pub enum Completion {
+    MinComplete(WeightFn),
+    MaxComplete(WeightFn)
+}
+
+pub struct Graph {
+    nodes: Vec<Node>,
+    // edges out of each node
+    edges: Vec<Vec<Edge>>,
+    index: HashMap<(NodeKind, String), usize>
+}
+
+// how long ago the edge was used, so min-complete picks the newest
+pub fn time_weight(_:&Node, edge:&Edge, now:u64) -> u64 {
+    if now > edge.last {now - edge.last} else {0}
+}
+
+// how often the edge was used, so max-complete picks the most common
+pub fn frequency_weight(_:&Node, edge:&Edge, _:u64) -> u64 {
+    edge.count as u64
+}
+
+// words of a line as it would be parsed, or split on spaces
+// if it doesn't parse on its own
+fn line_words(entry:&HistoryEntry) -> Vec<String> {
+    let context = match entry.to_input() {
+        None => None,
+        Some(line) => completion_context(&line)
+    };
+    let words = match context {
+        Some(c) => c.words,
+        None => entry.text.as_slice().split(' ').map(|s| s.to_string()).collect()
+    };
+    return words.into_iter().filter(|w| !w.trim().is_empty()).collect();
+}
+
+impl Graph {
+    pub fn new() -> Graph {
+        Graph {
+            nodes: vec![],
+            edges: vec![],
+            index: HashMap::new()
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn get(&self, id:usize) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn find(&self, kind:NodeKind, text:&str) -> Option<usize> {
+        self.index.get(&(kind, text.to_string())).map(|id| *id)
+    }
+
+    // find or add a node, and mark it used at time
+    pub fn node(&mut self, kind:NodeKind, text:&str, time:u64) -> usize {
+        let id = match self.find(kind, text) {
+            Some(id) => id,
+            None => {
+                self.nodes.push(Node {
+                    kind: kind,
+                    text: text.to_string(),
+                    count: 0,
+                    last: 0,
+                    status: 0
+                });
+                self.edges.push(vec![]);
+                self.index.insert((kind, text.to_string()), self.nodes.len() - 1);
+                self.nodes.len() - 1
+            }
+        };
+        let node = &mut self.nodes[id];
+        node.count += 1;
+        if time > node.last {
+            node.last = time;
+        }
+        return id;
+    }
+
+    pub fn connect(&mut self, from:usize, to:usize, kind:EdgeKind, time:u64) {
+        for edge in self.edges[from].iter_mut() {
+            if edge.to == to && edge.kind == kind {
+                edge.count += 1;
+                if time > edge.last {
+                    edge.last = time;
+                }
+                return;
+            }
+        }
+        self.edges[from].push(Edge {
+            kind: kind,
+            to: to,
+            count: 1,
+            last: time
+        });
+    }
+
+    // learn from a line that was run
+    pub fn record(&mut self, entry:&HistoryEntry) {
+        let time = entry.time;
+        let line = self.node(History, entry.text.as_slice(), time);
+        self.nodes[line].status = entry.status;
+        let dir = self.node(Directory, entry.cwd.as_slice(), time);
+        self.connect(dir, line, RunIn, time);
+        let words = line_words(entry);
+        let command = match words.first() {
+            None => return,
+            Some(w) => self.node(Command, w.as_slice(), time)
+        };
+        self.connect(dir, command, RunIn, time);
+        self.connect(command, line, Started, time);
+        for word in words[1..].iter() {
+            let arg = self.node(Argument, word.as_slice(), time);
+            self.connect(command, arg, Follows, time);
+        }
+    }
+
+    // neighbors of from of the given kind that start fuzzily matches,
+    // in the order completion picks them
+    pub fn complete(&self, from:usize, kind:NodeKind, completion:&Completion,
+                    now:u64, start:&str) -> Vec<usize> {
+        let mut found:Vec<(u64, &Edge)> = vec![];
+        for edge in self.edges[from].iter() {
+            let node = &self.nodes[edge.to];
+            if node.kind != kind || !matches(start, node.text.as_slice()) {
+                continue;
+            }
+            let weight = match completion {
+                &MinComplete(f) | &MaxComplete(f) => f(node, edge, now)
+            };
+            found.push((weight, edge));
+        }
+        found.sort_by(|&(aw, a), &(bw, b)| {
+            let order = match completion {
+                &MinComplete(_) => aw.cmp(&bw),
+                &MaxComplete(_) => bw.cmp(&aw)
+            };
+            match order {
+                // newer edges win ties
+                Ordering::Equal => b.last.cmp(&a.last),
+                o => o
+            }
+        });
+        return found.iter().map(|&(_, edge)| edge.to).collect();
+    }
+}
+
+#[cfg(test)]
+fn test_entry(text:&str, cwd:&str, time:u64) -> HistoryEntry {
+    HistoryEntry {
+        text: text.to_string(),
+        time: time,
+        cwd: cwd.to_string(),
+        status: 0,
+        duration: 0
+    }
+}
+
+#[cfg(test)]
+fn texts(graph:&Graph, ids:Vec<usize>) -> Vec<String> {
+    ids.iter().map(|id| graph.get(*id).text.clone()).collect()
+}
+
+#[test]
+fn graph_record_test() {
+    let mut graph = Graph::new();
+    graph.record(&test_entry("git status", "/src", 10));
+    graph.record(&test_entry("git status", "/src", 20));
+    let git = graph.find(Command, "git").unwrap();
+    assert_eq!(graph.get(git).count, 2);
+    assert!(graph.find(Argument, "status").is_some());
+    assert!(graph.find(Directory, "/src").is_some());
+    assert!(graph.find(History, "git status").is_some());
+    assert_eq!(graph.find(Command, "status"), None);
+}
+
+#[test]
+fn graph_complete_test() {
+    let mut graph = Graph::new();
+    graph.record(&test_entry("git push", "/src", 10));
+    graph.record(&test_entry("git push", "/src", 20));
+    graph.record(&test_entry("git push", "/src", 30));
+    graph.record(&test_entry("git pull", "/src", 40));
+    graph.record(&test_entry("git status", "/tmp", 50));
+    let git = graph.find(Command, "git").unwrap();
+    // push is used most, pull most recently
+    assert_eq!(texts(&graph, graph.complete(git, Argument, &MaxComplete(frequency_weight), 100, "p")),
+               vec!["push".to_string(), "pull".to_string()]);
+    assert_eq!(texts(&graph, graph.complete(git, Argument, &MinComplete(time_weight), 100, "p")),
+               vec!["pull".to_string(), "push".to_string()]);
+    // lines are remembered per directory
+    let src = graph.find(Directory, "/src").unwrap();
+    assert_eq!(texts(&graph, graph.complete(src, History, &MinComplete(time_weight), 100, "git")),
+               vec!["git pull".to_string(), "git push".to_string()]);
+}
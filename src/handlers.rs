@@ -3,6 +3,7 @@ use std::collections::*;
 use constants::*;
 use types::*;
 use ast::*;
+use error::*;
 
 use types::Action::*;
 use types::HandlerResult::*;
@@ -168,42 +169,56 @@ handler!(handle_dollarat, _, count, out, _, {
     Ok(Continue)
 });
 
-handler!(handle_semiamper, _, count, out, _, {
+handler!(handle_semiamper, _, count, out, ast, {
     if *count > 0 {
         out.push_back(Join(*count));
         *count = 0;
     }
     out.push_back(Call(format!("run")));
+    out.push_back(ast.strict_call());
+    // try! only covers the command before this
+    ast.unchecked = false;
     return Ok(Continue);
 });
 
-handler!(handle_amper, _, count, out, _, {
+// start the command before as a job, for & and the stages of a pipe
+fn push_job(count:&mut usize, out:&mut LinkedList<Action>) {
     if *count > 0 {
         out.push_back(Join(*count));
         *count = 0;
     }
     out.push_back(Call(format!("job")));
+}
+
+handler!(handle_amper, _, count, out, ast, {
+    push_job(count, out);
+    ast.unchecked = false;
     return Ok(Continue);
 });
 
-handler!(handle_amperamper, contents, count, out, ast, {
-    // amperamper is an extension of semiamper
-    try!(handle_semiamper(contents, count, out, ast));
+handler!(handle_amperamper, _, count, out, ast, {
+    if *count > 0 {
+        out.push_back(Join(*count));
+        *count = 0;
+    }
+    out.push_back(Call(format!("run")));
+    // the command failing only decides whether the rest runs
+    ast.unchecked = false;
     out.push_back(Call(format!("run_failed?")));
     let old_section = ast.new_section();
     let new_num = match ast.get_position() {
         SectionType::Number(n) => n,
         _ => panic!("New section wasn't a numbered one")
     };
-    ast.current_section().push_back(Fail(STOP.to_string()));
+    ast.current_section().push_back(Halt);
     ast.move_to(old_section);
     out.push_back(Branch(new_num));
     return Ok(Continue);
 });
 
-handler!(handle_bar, contents, count, out, ast, {
-    // extension of amper
-    try!(handle_amper(contents, count, out, ast));
+handler!(handle_bar, _, count, out, _, {
+    // extension of amper, but try! covers the whole pipe
+    push_job(count, out);
     out.push_back(Insert(WashArgs::Flat(format!("$pipe:"))));
     out.push_back(Temp);
     out.push_back(Join(2));
@@ -221,7 +236,7 @@ handler!(handle_geq, contents, count, out, ast, {
     }
     out.push_back(Temp);
     if contents.is_empty() {
-        return Err(format!("No file name given"));
+        return Err(WashError::parse(format!("No file name given")));
     } else {
         let mut value = match contents.pop_front().unwrap() {
             Split(_) if !contents.is_empty() => contents.pop_front().unwrap(),
@@ -229,7 +244,7 @@ handler!(handle_geq, contents, count, out, ast, {
         };
         let mut aclist = try!(ast.process(&mut value, false));
         if aclist.is_empty() {
-            return Err(format!("No file name given"));
+            return Err(WashError::parse(format!("No file name given")));
         }
         out.append(&mut aclist);
         out.push_back(Call(format!("open_output")));
@@ -248,7 +263,7 @@ handler!(handle_leq, contents, count, out, ast, {
     }
     out.push_back(Temp);
     if contents.is_empty() {
-        return Err(format!("No file name given"));
+        return Err(WashError::parse(format!("No file name given")));
     } else {
         let mut value = match contents.pop_front().unwrap() {
             Split(_) if !contents.is_empty() => contents.pop_front().unwrap(),
@@ -256,7 +271,7 @@ handler!(handle_leq, contents, count, out, ast, {
         };
         let mut aclist = try!(ast.process(&mut value, false));
         if aclist.is_empty() {
-            return Err(format!("No file name given"));
+            return Err(WashError::parse(format!("No file name given")));
         }
         out.append(&mut aclist);
         out.push_back(Call(format!("open_input")));
@@ -308,7 +323,7 @@ handler!(handle_if, contents, count, out, ast, {
 
 handler!(handle_elif, contents, count, out, ast, {
     let old_section = match ast.elif {
-        None => return Err(format!("No proceeding if block for elif")),
+        None => return Err(WashError::parse(format!("No proceeding if block for elif"))),
         Some(s) => s
     };
     ast.current_section().append(out);
@@ -350,7 +365,7 @@ handler!(handle_elif, contents, count, out, ast, {
 handler!(handle_else, contents, count, out, ast, {
     ast.current_section().append(out);
     let old_section = match ast.elif {
-        None => return Err(format!("No proceeding if block for else")),
+        None => return Err(WashError::parse(format!("No proceeding if block for else"))),
         Some(s) => s
     };
     loop {
@@ -453,7 +468,14 @@ handler!(handle_act, contents, count, out, ast, {
     return Ok(More(SectionType::Number(finalsec)));
 });
 
+handler!(handle_try, _, _, _, ast, {
+    // the command on the rest of this line is allowed to fail
+    ast.unchecked = true;
+    return Ok(Continue);
+});
+
 handler!(handle_endblock, _, count, out, ast, {
+    let describe = ast.describe_call();
     {
         let mut sec = ast.current_section();
         sec.append(out);
@@ -464,7 +486,7 @@ handler!(handle_endblock, _, count, out, ast, {
                 sec.push_back(Pull);
             }
             sec.push_back(Call(format!("run")));
-            sec.push_back(Call(format!("describe_process_output")));
+            sec.push_back(describe);
         }
     }
     try!(ast.end_block());
@@ -492,4 +514,26 @@ pub fn load_handlers(ast:&mut AST) {
     ast.add_handler("while!", handle_while);
     ast.add_handler("func!", handle_func);
     ast.add_handler("}", handle_endblock);
+    ast.add_handler("try!", handle_try);
+}
+
+#[test]
+fn try_pipe_test() {
+    use input::*;
+    use env::*;
+    use source::*;
+    let mut env = WashEnv::new();
+    ::builtins::load_builtins(&mut env).unwrap();
+    let mut ast = AST::new();
+    load_handlers(&mut ast);
+    let mut line = InputLine::new();
+    for ch in "try! echo a | false".chars() {
+        assert!(line.push(ch));
+    }
+    // the last stage failing is covered by try! too
+    match run_input(&line, &mut ast, &mut env) {
+        Some(Ok(_)) => {},
+        _ => panic!("try! pipe failed")
+    }
+    assert!(env.exit_status() != 0);
 }
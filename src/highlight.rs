@@ -0,0 +1,195 @@
+// Syntax highlighting for the line being edited: splits the text into
+// words the way InputLine does and says what each one is
+use std::collections::*;
+use std::fs::PathExt;
+use std::path::Path;
+use std::env;
+
+use constants::*;
+use env::*;
+use error::*;
+
+use self::HighlightKind::*;
+
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+pub enum HighlightKind {
+    // a command or function that can be run
+    Command,
+    // one that can't be found
+    Missing,
+    Literal,
+    Variable,
+    // a handler word, like if! or |
+    Keyword,
+    // a parenthesis without a partner
+    Unbalanced,
+    // the rest of a line from history, after the cursor
+    Suggestion
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Span {
+    // in characters
+    pub start: usize,
+    pub end: usize,
+    pub kind: HighlightKind
+}
+
+// handlers after which the next word is a command again
+const COMMAND_SEPARATORS:[&'static str; 4] = ["|", "&", "&&", "&;"];
+
+impl HighlightKind {
+    pub fn from_name(name:&str) -> Option<HighlightKind> {
+        match name {
+            "command" => Some(Command),
+            "missing" => Some(Missing),
+            "literal" => Some(Literal),
+            "variable" => Some(Variable),
+            "keyword" => Some(Keyword),
+            "unbalanced" => Some(Unbalanced),
+            "suggestion" => Some(Suggestion),
+            _ => None
+        }
+    }
+}
+
+// $cfg:colors, which is kind=code pairs where code is a select
+// graphic rendition code like 32 or 1;31
+pub fn parse_colors(s:&str) -> WashResult<HashMap<HighlightKind, String>> {
+    let mut out = HashMap::new();
+    for pair in s.split(' ').filter(|p| !p.is_empty()) {
+        let (name, code) = match pair.find('=') {
+            None => return Err(WashError::runtime(format!("Colors must be written kind=code: {}", pair))),
+            Some(i) => (&pair[..i], &pair[i + 1..])
+        };
+        let kind = match HighlightKind::from_name(name) {
+            None => return Err(WashError::runtime(format!("Unknown kind of text: {}", name))),
+            Some(k) => k
+        };
+        if code.is_empty() || !code.chars().all(|c| c == ';' || c.is_digit(10)) {
+            return Err(WashError::runtime(format!("Not a color code: {}", code)));
+        }
+        out.insert(kind, code.to_string());
+    }
+    return Ok(out);
+}
+
+fn is_break(ch:char) -> bool {
+    match ch {
+        SPC | CMA | NL | OPR | CPR => true,
+        _ => false
+    }
+}
+
+// everything worth coloring in text, in order. Commands are all given
+// as Command, and check_commands sorts out which are missing
+pub fn highlight(text:&Vec<char>, handlers:&Vec<String>) -> Vec<Span> {
+    let mut out = vec![];
+    let mut opened = vec![];
+    // the next word is first on its line
+    let mut first = true;
+    let mut i = 0;
+    while i < text.len() {
+        match text[i] {
+            NL if opened.is_empty() => {
+                first = true;
+                i += 1;
+                continue;
+            },
+            OPR => {
+                opened.push(i);
+                i += 1;
+                continue;
+            },
+            CPR => {
+                if opened.pop().is_none() {
+                    out.push(Span {start: i, end: i + 1, kind: Unbalanced});
+                }
+                i += 1;
+                continue;
+            },
+            c if is_break(c) => {
+                i += 1;
+                continue;
+            },
+            _ => {}
+        }
+        // one word, where quotes keep it going past breaks
+        let mut end = i;
+        let mut literal = false;
+        while end < text.len() && (literal || !is_break(text[end])) {
+            if text[end] == QUT {
+                literal = !literal;
+            }
+            end += 1;
+        }
+        let word:String = text[i..end].iter().map(|c| *c).collect();
+        let kind = if word.contains(QUT.to_string().as_slice()) {
+            Some(Literal)
+        } else if word.starts_with("$") {
+            Some(Variable)
+        } else if handlers.contains(&word) {
+            Some(Keyword)
+        } else if first || (end < text.len() && text[end] == OPR) {
+            // first on the line, or called like a function
+            Some(Command)
+        } else {
+            None
+        };
+        first = kind == Some(Keyword) && COMMAND_SEPARATORS.contains(&word.as_slice());
+        match kind {
+            None => {},
+            Some(k) => out.push(Span {start: i, end: end, kind: k})
+        }
+        i = end;
+    }
+    for start in opened.into_iter() {
+        out.push(Span {start: start, end: start + 1, kind: Unbalanced});
+    }
+    out.sort_by(|a, b| a.start.cmp(&b.start));
+    return out;
+}
+
+// a function, or a program in $PATH or at a path
+pub fn command_exists(name:&str, env:&WashEnv) -> bool {
+    if env.functions.contains_key(name) {
+        return true;
+    } else if name.contains("/") {
+        return Path::new(name).is_file();
+    }
+    let path = match env::var("PATH") {
+        Err(_) => return false,
+        Ok(p) => p
+    };
+    return path.as_slice().split(':').any(|dir| Path::new(dir).join(name).is_file());
+}
+
+pub fn check_commands(spans:&mut Vec<Span>, text:&Vec<char>, env:&WashEnv) {
+    for span in spans.iter_mut() {
+        if span.kind == Command {
+            let name:String = text[span.start..span.end].iter().map(|c| *c).collect();
+            if !command_exists(name.as_slice(), env) {
+                span.kind = Missing;
+            }
+        }
+    }
+}
+
+#[test]
+fn highlight_test() {
+    let handlers = vec!["|".to_string(), "if!".to_string()];
+    let text:Vec<char> = "ls \"a b\" $x | grep(y)) (".chars().collect();
+    let spans = highlight(&text, &handlers);
+    let kinds:Vec<(usize, usize, HighlightKind)> = spans.iter().map(|s| (s.start, s.end, s.kind)).collect();
+    assert_eq!(kinds, vec![(0, 2, Command), (3, 8, Literal), (9, 11, Variable), (12, 13, Keyword),
+                           (14, 18, Command), (21, 22, Unbalanced), (23, 24, Unbalanced)]);
+}
+
+#[test]
+fn parse_colors_test() {
+    let colors = parse_colors("command=32 unbalanced=1;41").unwrap();
+    assert_eq!(colors.get(&Command), Some(&"32".to_string()));
+    assert_eq!(colors.get(&Unbalanced), Some(&"1;41".to_string()));
+    assert!(parse_colors("command=green").is_err());
+    assert!(parse_colors("shell=32").is_err());
+}
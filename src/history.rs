@@ -0,0 +1,236 @@
+// The history file, where every line run interactively is kept along
+// with when and where it ran and how it went
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::collections::*;
+use std::num::*;
+use std::env;
+
+use input::*;
+use env::*;
+use ioctl::*;
+use util::*;
+
+// status of a line that was saved before it ran, and never finished
+pub const UNFINISHED:i32 = -1;
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct HistoryEntry {
+    pub text: String,
+    // milliseconds since the epoch
+    pub time: u64,
+    pub cwd: String,
+    pub status: i32,
+    // milliseconds
+    pub duration: u64
+}
+
+impl HistoryEntry {
+    // one line of the history file:
+    // time, duration, status, cwd and text separated by tabs
+    pub fn to_line(&self) -> String {
+        format!("{}\t{}\t{}\t{}\t{}\n", self.time, self.duration, self.status,
+                escape_field(&self.cwd), escape_field(&self.text))
+    }
+
+    pub fn from_line(line:&str) -> Option<HistoryEntry> {
+        // tabs in fields are escaped, so this is safe
+        let fields:Vec<&str> = line.trim_right_matches('\n').split('\t').collect();
+        if fields.len() != 5 {
+            return None;
+        }
+        Some(HistoryEntry {
+            time: match from_str_radix(fields[0], 10) {
+                Err(_) => return None,
+                Ok(v) => v
+            },
+            duration: match from_str_radix(fields[1], 10) {
+                Err(_) => return None,
+                Ok(v) => v
+            },
+            status: match from_str_radix(fields[2], 10) {
+                Err(_) => return None,
+                Ok(v) => v
+            },
+            cwd: unescape_field(fields[3]),
+            text: unescape_field(fields[4])
+        })
+    }
+
+    // rebuild the line by typing it in again
+    pub fn to_input(&self) -> Option<InputLine> {
+        let mut line = InputLine::new();
+        for ch in self.text.chars() {
+            if !line.push(ch) {
+                return None;
+            }
+        }
+        return Some(line);
+    }
+}
+
+// tabs and newlines would break up the file
+fn escape_field(s:&String) -> String {
+    let mut out = String::new();
+    for ch in s.chars() {
+        match ch {
+            '\\' => out.push_str("\\\\"),
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c)
+        }
+    }
+    return out;
+}
+
+fn unescape_field(s:&str) -> String {
+    let mut out = String::new();
+    let mut escaped = false;
+    for ch in s.chars() {
+        if escaped {
+            out.push(match ch {
+                't' => '\t',
+                'n' => '\n',
+                c => c
+            });
+            escaped = false;
+        } else if ch == '\\' {
+            escaped = true;
+        } else {
+            out.push(ch);
+        }
+    }
+    return out;
+}
+
+pub fn history_path(env:&WashEnv) -> Option<PathBuf> {
+    let name = env.settings.text("history_file");
+    if name.is_empty() {
+        // history file is turned off
+        return None;
+    }
+    return Some(expand_path(PathBuf::new(name.as_slice())));
+}
+
+// entries in the order they were run, lines that can't be read are skipped
+pub fn load_history(path:&Path) -> io::Result<Vec<HistoryEntry>> {
+    let mut contents = String::new();
+    let mut file = try!(File::open(path));
+    try!(file.read_to_string(&mut contents));
+    return Ok(parse_history(contents.as_slice()));
+}
+
+// lines are saved once when they start and again when they finish,
+// the second replaces the first
+pub fn parse_history(contents:&str) -> Vec<HistoryEntry> {
+    let mut out:Vec<HistoryEntry> = vec![];
+    // unfinished entries by when they started and their text
+    let mut started = HashMap::new();
+    for line in contents.lines() {
+        let entry = match HistoryEntry::from_line(line) {
+            None => continue,
+            Some(e) => e
+        };
+        let key = (entry.time, entry.text.clone());
+        match started.remove(&key) {
+            Some(i) => out[i] = entry,
+            None => {
+                if entry.status == UNFINISHED {
+                    started.insert(key, out.len());
+                }
+                out.push(entry);
+            }
+        }
+    }
+    return out;
+}
+
+// entries are appended as they're run, so nothing is lost if wash dies
+pub fn append_history(path:&Path, entry:&HistoryEntry) -> io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).append(true).create(true);
+    let mut file = try!(options.open(path));
+    try!(file.write_all(entry.to_line().as_bytes()));
+    return Ok(());
+}
+
+fn save_history(entry:&HistoryEntry, env:&mut WashEnv) {
+    match history_path(env) {
+        None => {/* not kept between sessions */},
+        Some(path) => match append_history(path.as_path(), entry) {
+            Err(e) => env.errf(format_args!("Couldn't save history: {}\n", e)),
+            Ok(_) => {}
+        }
+    }
+}
+
+// save a line that's about to run, from where it's run
+pub fn begin_history(text:String, env:&mut WashEnv) -> Option<HistoryEntry> {
+    if text.trim().is_empty() {
+        return None;
+    }
+    let entry = HistoryEntry {
+        text: text,
+        time: get_time_ms(),
+        cwd: match env::current_dir() {
+            Err(_) => String::new(),
+            Ok(p) => format!("{}", p.display())
+        },
+        status: UNFINISHED,
+        duration: 0
+    };
+    save_history(&entry, env);
+    return Some(entry);
+}
+
+// save it again once it's finished, with how it went
+pub fn end_history(mut entry:HistoryEntry, env:&mut WashEnv) -> HistoryEntry {
+    let now = get_time_ms();
+    entry.status = env.exit_status();
+    entry.duration = if now > entry.time {now - entry.time} else {0};
+    save_history(&entry, env);
+    return entry;
+}
+
+#[test]
+fn history_line_test() {
+    let entry = HistoryEntry {
+        text: "echo \"a\tb\nc\" \\".to_string(),
+        time: 1428000000000,
+        cwd: "/home/wash".to_string(),
+        status: 1,
+        duration: 25
+    };
+    let line = entry.to_line();
+    assert_eq!(line.as_slice().lines().count(), 1);
+    assert_eq!(HistoryEntry::from_line(line.as_slice()), Some(entry));
+}
+
+#[test]
+fn history_bad_line_test() {
+    assert_eq!(HistoryEntry::from_line("not a history line"), None);
+    assert_eq!(HistoryEntry::from_line("1\t2\tx\t/\tls"), None);
+}
+
+#[test]
+fn parse_history_test() {
+    let mut entry = HistoryEntry {
+        text: "cd /tmp".to_string(),
+        time: 100,
+        cwd: "/home/wash".to_string(),
+        status: UNFINISHED,
+        duration: 0
+    };
+    let mut crashed = entry.clone();
+    crashed.text = "make".to_string();
+    crashed.time = 200;
+    let mut contents = entry.to_line();
+    contents.push_str(crashed.to_line().as_slice());
+    entry.status = 0;
+    entry.duration = 5;
+    contents.push_str(entry.to_line().as_slice());
+    // the finished entry takes the place of the started one, in the
+    // directory it started in, and the one that never finished is kept
+    assert_eq!(parse_history(contents.as_slice()), vec![entry, crashed]);
+}
@@ -8,7 +8,9 @@ pub struct InputLine {
     pub back: Vec<InputValue>,
     pub front: InputValue,
     pub part: String,
-    pub fpart: String
+    pub fpart: String,
+    // where the line being input started
+    pub pos: SourcePos
 }
 
 impl InputLine {
@@ -17,7 +19,8 @@ impl InputLine {
             back: vec![Long(vec![])],
             front: Short(String::new()),
             part: String::new(),
-            fpart: String::new()
+            fpart: String::new(),
+            pos: SourcePos::new("input", 1)
         }
     }
     
@@ -744,6 +747,13 @@ impl InputLine {
         }
     }
 
+    // the whole line as typed, ignoring the cursor
+    pub fn text(&self) -> String {
+        let mut out = self.fpart.clone();
+        out.extend(self.part.chars().rev());
+        return out;
+    }
+
     pub fn process(&self) -> Option<InputValue> {
         let mut output = self.clone();
         loop {
@@ -772,6 +782,39 @@ impl InputLine {
     }
 }
 
+// characters that end a word for word motion and killing,
+// the same ones that split up values in InputLine
+pub fn is_word_break(ch:char) -> bool {
+    match ch {
+        SPC | CMA | OPR | CPR | QUT | NL => true,
+        _ => false
+    }
+}
+
+// start of the word before pos, in characters
+pub fn word_back(text:&Vec<char>, pos:usize) -> usize {
+    let mut i = pos;
+    while i > 0 && is_word_break(text[i - 1]) {
+        i -= 1;
+    }
+    while i > 0 && !is_word_break(text[i - 1]) {
+        i -= 1;
+    }
+    return i;
+}
+
+// end of the word after pos
+pub fn word_forward(text:&Vec<char>, pos:usize) -> usize {
+    let mut i = pos;
+    while i < text.len() && is_word_break(text[i]) {
+        i += 1;
+    }
+    while i < text.len() && !is_word_break(text[i]) {
+        i += 1;
+    }
+    return i;
+}
+
 #[cfg(test)]
 fn test_input_against(line:String, against:InputValue) -> bool {
     // test winding
@@ -1040,3 +1083,14 @@ fn test_input_long_space() {
                                                                        Short(format!("args"))
                                                                        ])])])])));
 }
+
+#[test]
+fn test_input_word_motion() {
+    let text:Vec<char> = "func(some-arg, \"a b\")".chars().collect();
+    assert_eq!(word_forward(&text, 0), 4);
+    assert_eq!(word_forward(&text, 4), 13);
+    assert_eq!(word_forward(&text, 13), 17);
+    assert_eq!(word_back(&text, 13), 5);
+    assert_eq!(word_back(&text, 5), 0);
+    assert_eq!(word_back(&text, text.len()), 18);
+}
@@ -43,6 +43,13 @@ pub struct TM {
     _tmzone: *const c_char
 }
 
+#[derive(Copy)]
+#[repr(C)]
+struct TimeVal {
+    sec: time_t,
+    usec: c_long
+}
+
 #[link(name="c")]
 extern {
     fn ioctl(d:Fd, request:c_ulong, ...) -> c_int;
@@ -51,6 +58,8 @@ extern {
                 tm:*const TM) -> size_t;
     fn time(t:*mut time_t) -> time_t;
     fn localtime(timep:*const time_t) -> *const TM;
+    fn isatty(fd:Fd) -> c_int;
+    fn gettimeofday(tv:*mut TimeVal, tz:*mut c_void) -> c_int;
 }
 
 pub fn term_winsize() -> io::Result<WinSize> {
@@ -61,6 +70,10 @@ pub fn term_winsize() -> io::Result<WinSize> {
     }
 }
 
+pub fn is_tty(fd:Fd) -> bool {
+    unsafe {isatty(fd) == 1}
+}
+
 pub fn get_hostname() -> io::Result<String> {
     let mut name = [1; HOST_NAME_MAX];
     match unsafe {gethostname(name.as_mut_ptr(), HOST_NAME_MAX as u64)} {
@@ -89,13 +102,20 @@ pub fn get_time() -> Option<TM> {
     }
 }
 
-pub fn strf_time(format:&String, time:&TM) -> String {
-    let mut out = [1; STRF_BUF_SIZE];
+// milliseconds since the epoch
+pub fn get_time_ms() -> u64 {
+    let mut tv = TimeVal {sec: 0, usec: 0};
+    unsafe {gettimeofday(&mut tv, 0 as *mut c_void)};
+    return (tv.sec as u64) * 1000 + (tv.usec as u64) / 1000;
+}
+
+pub fn strf_time(format:&String, time:&TM, size:usize) -> String {
+    let mut out = vec![1; size];
     let format_cstr = match ffi::CString::new(format.as_slice()) {
         Err(e) => panic!("Could not create CString from format: {}", e),
         Ok(s) => s
     };
-    match unsafe {strftime(out.as_mut_ptr(), STRF_BUF_SIZE as u64,
+    match unsafe {strftime(out.as_mut_ptr(), size as u64,
                            format_cstr.as_ptr(), time)} {
         0 => return String::new(), // contents of out may be undefined
         _ => return String::from_utf8_lossy(unsafe {
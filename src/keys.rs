@@ -0,0 +1,431 @@
+// Key notation, shared by the bind builtin and key_helper. Keys are
+// written the way emacs writes them: C-a for control, M-f for alt (or
+// ESC before the key), and names for keys that don't print, like RET,
+// TAB and Up. Sequences of keys have spaces between them, like C-x C-e.
+// This also decodes what terminals send into keys
+use std::ascii::AsciiExt;
+
+use self::Decoded::*;
+
+const ESC:char = '\u{1b}';
+// around pasted text, after the ESC
+const PASTE_START:&'static str = "[200~";
+const PASTE_END:&'static str = "\u{1b}[201~";
+
+// keys with names that send a single character
+const CHAR_KEYS:[(&'static str, char); 5] = [
+    ("RET", '\n'),
+    ("TAB", '\t'),
+    ("DEL", '\u{7f}'),
+    ("ESC", '\u{1b}'),
+    ("SPC", ' ')
+];
+
+// keys terminals send escape sequences for, which can be held down with
+// control, alt and shift. These are written C-M-S-Up
+const FUNCTION_KEYS:[&'static str; 22] = [
+    "Up", "Down", "Right", "Left", "Home", "End", "Insert", "Delete", "PgUp", "PgDn",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12"
+];
+
+// what the terminal sent, turned into keys
+#[derive(Clone, PartialEq, Debug)]
+pub enum Decoded {
+    // more characters are needed to know
+    Pending,
+    Key(String),
+    // ESC [ row ; col R, the answer to a cursor position query
+    CursorReport(usize, usize),
+    // everything between ESC [ 200 ~ and ESC [ 201 ~
+    Paste(String),
+    // a sequence we don't know, as sent
+    Unknown(String)
+}
+
+// Turns characters from the terminal into keys. A sequence starts with
+// ESC, then [ (CSI) or O (SS3), parameters and a final character, which
+// is the only way to tell arrow keys from ESC typed before [. A bare ESC
+// is only known by nothing coming after it, so whoever is reading calls
+// flush when no more input comes in time
+pub struct KeyDecoder {
+    escape: bool,
+    // everything after the ESC
+    seq: String,
+    // text pasted so far, while in the middle of a paste
+    paste: Option<String>,
+    // longest sequence before giving up
+    pub max: usize
+}
+
+// name of the key that sends ch
+pub fn char_key(ch:char) -> String {
+    match ch {
+        '\n' | '\r' => "RET".to_string(),
+        '\t' => "TAB".to_string(),
+        '\u{7f}' => "DEL".to_string(),
+        '\u{1b}' => "ESC".to_string(),
+        ' ' => "SPC".to_string(),
+        '\u{0}' => "C-@".to_string(),
+        c if (c as u32) < 0x1b => format!("C-{}", ((c as u8) + b'a' - 1) as char),
+        c if (c as u32) < 0x20 => format!("C-{}", ((c as u8) + b'@') as char),
+        c => c.to_string()
+    }
+}
+
+// the character a key types, if it's one that prints
+pub fn key_char(key:&str) -> Option<char> {
+    if key == "SPC" {
+        return Some(' ');
+    }
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_control() => Some(c),
+        _ => None
+    }
+}
+
+// the key with alt held down as well
+pub fn add_meta(key:String) -> String {
+    if key.starts_with("C-") {
+        format!("C-M-{}", &key[2..])
+    } else {
+        format!("M-{}", key)
+    }
+}
+
+fn with_modifiers(name:&str, ctrl:bool, meta:bool, shift:bool) -> String {
+    format!("{}{}{}{}", if ctrl {"C-"} else {""}, if meta {"M-"} else {""},
+            if shift {"S-"} else {""}, name)
+}
+
+fn control_char(ch:char) -> Option<char> {
+    match ch.to_ascii_lowercase() {
+        c @ 'a'...'z' => Some(((c as u8) - b'a' + 1) as char),
+        '@' | ' ' => Some('\u{0}'),
+        '[' => Some('\u{1b}'),
+        '\\' => Some('\u{1c}'),
+        ']' => Some('\u{1d}'),
+        '^' => Some('\u{1e}'),
+        '_' => Some('\u{1f}'),
+        '?' => Some('\u{7f}'),
+        _ => None
+    }
+}
+
+// one key as written by someone, in the form the line editor uses
+pub fn parse_key(s:&str) -> Option<String> {
+    // modifiers can come in any order
+    let (mut ctrl, mut meta, mut shift) = (false, false, false);
+    let mut rest = s;
+    while rest.len() > 2 {
+        if rest.starts_with("C-") {
+            ctrl = true;
+        } else if rest.starts_with("M-") {
+            meta = true;
+        } else if rest.starts_with("S-") {
+            shift = true;
+        } else {
+            break;
+        }
+        rest = &rest[2..];
+    }
+    let lower = rest.to_ascii_lowercase();
+    for name in FUNCTION_KEYS.iter() {
+        if lower == name.to_ascii_lowercase() {
+            return Some(with_modifiers(*name, ctrl, meta, shift));
+        }
+    }
+    // everything else sends a character, which control changes
+    let mut sent = None;
+    for &(name, ch) in CHAR_KEYS.iter() {
+        if lower == name.to_ascii_lowercase() {
+            sent = Some(ch);
+        }
+    }
+    let chars:Vec<char> = rest.chars().collect();
+    if sent.is_none() && chars.len() == 1 && !chars[0].is_control() {
+        sent = Some(chars[0]);
+    }
+    let mut ch = match sent {
+        None => return None,
+        Some(c) => c
+    };
+    if ctrl {
+        // C-i is TAB, C-m is RET, and so on
+        ch = match control_char(ch) {
+            None => return None,
+            Some(c) => c
+        };
+    }
+    if shift {
+        // shift only changes what TAB sends
+        if ch != '\t' {
+            return None;
+        }
+        return Some(with_modifiers("TAB", false, meta, true));
+    }
+    let key = char_key(ch);
+    return Some(if meta {add_meta(key)} else {key});
+}
+
+// a sequence of keys separated by spaces
+pub fn parse_keys(s:&str) -> Option<Vec<String>> {
+    let mut out = vec![];
+    for part in s.split(' ').filter(|p| !p.is_empty()) {
+        match parse_key(part) {
+            None => return None,
+            Some(key) => out.push(key)
+        }
+    }
+    if out.is_empty() {
+        return None;
+    }
+    return Some(out);
+}
+
+fn parse_number(s:&str) -> Option<usize> {
+    let mut out = 0;
+    for ch in s.chars() {
+        match ch.to_digit(10) {
+            None => return None,
+            Some(d) => out = out * 10 + d as usize
+        }
+    }
+    return Some(out);
+}
+
+// the key for a whole sequence, seq being everything after the ESC
+fn decode_sequence(seq:&str) -> Decoded {
+    let unknown = Unknown(format!("{}{}", ESC, seq));
+    let chars:Vec<char> = seq.chars().collect();
+    let (intro, last) = (chars[0], chars[chars.len() - 1]);
+    // parameters are numbers separated by ;, and empty ones are left out
+    let mut params = vec![];
+    for param in seq[1..seq.len() - 1].split(';') {
+        if param.is_empty() {
+            params.push(None);
+        } else {
+            match parse_number(param) {
+                None => return unknown,
+                Some(n) => params.push(Some(n))
+            }
+        }
+    }
+    let first = params[0];
+    if intro == '[' && last == 'R' {
+        return match (first, params.get(1)) {
+            (Some(row), Some(&Some(col))) if params.len() == 2 => CursorReport(row, col),
+            _ => unknown
+        };
+    }
+    // the modifier parameter is 1 plus shift 1, alt 2, control 4 (and meta 8)
+    let modifier = match (params.len(), last) {
+        (1, '~') => 1,
+        (1, _) if first.is_none() => 1,
+        (2, '~') | (2, 'A'...'Z') => params[1].unwrap_or(1),
+        _ => return unknown
+    };
+    if last != '~' && first.is_some() && first != Some(1) {
+        // letter keys only take a modifier, after a 1
+        return unknown;
+    }
+    let name = match (intro, last, first) {
+        ('[', 'Z', None) => return Key("S-TAB".to_string()),
+        (_, 'A', _) => "Up",
+        (_, 'B', _) => "Down",
+        (_, 'C', _) => "Right",
+        (_, 'D', _) => "Left",
+        (_, 'H', _) => "Home",
+        (_, 'F', _) => "End",
+        (_, 'P', _) => "F1",
+        (_, 'Q', _) => "F2",
+        ('O', 'R', _) => "F3",
+        (_, 'S', _) => "F4",
+        ('[', '~', Some(key)) => match key {
+            1 | 7 => "Home",
+            2 => "Insert",
+            3 => "Delete",
+            4 | 8 => "End",
+            5 => "PgUp",
+            6 => "PgDn",
+            11 => "F1",
+            12 => "F2",
+            13 => "F3",
+            14 => "F4",
+            15 => "F5",
+            17 => "F6",
+            18 => "F7",
+            19 => "F8",
+            20 => "F9",
+            21 => "F10",
+            23 => "F11",
+            24 => "F12",
+            _ => return unknown
+        },
+        _ => return unknown
+    };
+    let bits = if modifier > 0 {modifier - 1} else {0};
+    return Key(with_modifiers(name, bits & 4 != 0, bits & 10 != 0, bits & 1 != 0));
+}
+
+impl KeyDecoder {
+    pub fn new(max:usize) -> KeyDecoder {
+        KeyDecoder {
+            escape: false,
+            seq: String::new(),
+            paste: None,
+            max: max
+        }
+    }
+
+    // in the middle of a sequence, so flush should be called if
+    // nothing else comes
+    pub fn pending(&self) -> bool {
+        self.escape && self.paste.is_none()
+    }
+
+    pub fn reset(&mut self) {
+        self.escape = false;
+        self.seq.clear();
+        self.paste = None;
+    }
+
+    pub fn push(&mut self, ch:char) -> Decoded {
+        let ended = match self.paste {
+            None => false,
+            Some(ref mut text) => {
+                text.push(ch);
+                text.ends_with(PASTE_END)
+            }
+        };
+        if ended {
+            let mut text = self.paste.take().unwrap();
+            let len = text.len() - PASTE_END.len();
+            text.truncate(len);
+            return Paste(text);
+        } else if self.paste.is_some() {
+            return Pending;
+        }
+        if !self.escape {
+            if ch == ESC {
+                self.escape = true;
+                return Pending;
+            }
+            return Key(char_key(ch));
+        }
+        if self.seq.is_empty() {
+            if ch == '[' || ch == 'O' {
+                self.seq.push(ch);
+                return Pending;
+            }
+            // alt and a key
+            self.reset();
+            return Key(add_meta(char_key(ch)));
+        }
+        self.seq.push(ch);
+        if ch >= '@' && ch <= '~' {
+            // the final character
+            let out = if self.seq == PASTE_START {
+                self.paste = Some(String::new());
+                Pending
+            } else {
+                decode_sequence(self.seq.as_slice())
+            };
+            self.escape = false;
+            self.seq.clear();
+            return out;
+        } else if ch < ' ' || ch > '?' || self.seq.len() > self.max {
+            let out = Unknown(format!("{}{}", ESC, self.seq));
+            self.reset();
+            return out;
+        }
+        return Pending;
+    }
+
+    // what's been sent so far, when nothing more is coming
+    pub fn flush(&mut self) -> Decoded {
+        if !self.escape {
+            return Pending;
+        }
+        let seq = self.seq.clone();
+        self.reset();
+        let mut chars = seq.chars();
+        match (chars.next(), chars.next()) {
+            (None, _) => Key("ESC".to_string()),
+            // alt and [ or O
+            (Some(c), None) => Key(add_meta(char_key(c))),
+            _ => Unknown(format!("{}{}", ESC, seq))
+        }
+    }
+}
+
+// write out what a terminal sent as keys
+pub fn describe_input(s:&str) -> String {
+    let mut decoder = KeyDecoder::new(s.len());
+    let mut keys:Vec<String> = vec![];
+    let mut decoded:Vec<Decoded> = s.chars().map(|c| decoder.push(c)).collect();
+    decoded.push(decoder.flush());
+    for out in decoded.into_iter() {
+        match out {
+            Key(key) => keys.push(key),
+            // unknown sequences are written out a key at a time
+            Unknown(seq) => keys.extend(seq.chars().map(char_key)),
+            CursorReport(row, col) => keys.push(format!("ESC [ {} ; {} R", row, col)),
+            Paste(text) => keys.extend(text.chars().map(char_key)),
+            Pending => {}
+        }
+    }
+    return keys.connect(" ");
+}
+
+#[test]
+fn parse_keys_test() {
+    assert_eq!(parse_keys("C-x C-e"), Some(vec!["C-x".to_string(), "C-e".to_string()]));
+    assert_eq!(parse_keys("C-i"), Some(vec!["TAB".to_string()]));
+    assert_eq!(parse_keys("M-f up"), Some(vec!["M-f".to_string(), "Up".to_string()]));
+    assert_eq!(parse_keys("C-M-up M-C-a c-spc"), Some(vec!["C-M-Up".to_string(), "C-M-a".to_string(),
+                                                         "C-@".to_string()]));
+    assert_eq!(parse_keys("S-tab"), Some(vec!["S-TAB".to_string()]));
+    assert_eq!(parse_keys("S-a"), None);
+    assert_eq!(parse_keys("C-"), None);
+    assert_eq!(parse_keys(""), None);
+}
+
+#[test]
+fn describe_input_test() {
+    assert_eq!(describe_input("\u{1}"), "C-a".to_string());
+    assert_eq!(describe_input("\u{1b}[A"), "Up".to_string());
+    assert_eq!(describe_input("\u{1b}f"), "M-f".to_string());
+    assert_eq!(describe_input("a b"), "a SPC b".to_string());
+    assert_eq!(describe_input("\u{1b}[9Z"), "ESC [ 9 Z".to_string());
+    assert_eq!(describe_input("\u{1b}[1;5C\u{1b}[3~\u{1b}OH"), "C-Right Delete Home".to_string());
+    assert_eq!(describe_input("\u{1b}[15;3~\u{1b}\u{1}"), "M-F5 C-M-a".to_string());
+    assert_eq!(describe_input("\u{1b}"), "ESC".to_string());
+    assert_eq!(describe_input("\u{1b}["), "M-[".to_string());
+}
+
+#[test]
+fn key_decoder_test() {
+    let mut decoder = KeyDecoder::new(15);
+    assert_eq!(decoder.push('\u{1b}'), Pending);
+    assert!(decoder.pending());
+    assert_eq!(decoder.push('['), Pending);
+    assert_eq!(decoder.push('1'), Pending);
+    assert_eq!(decoder.push('2'), Pending);
+    assert_eq!(decoder.push(';'), Pending);
+    assert_eq!(decoder.push('4'), Pending);
+    assert_eq!(decoder.push('R'), CursorReport(12, 4));
+    assert!(!decoder.pending());
+    assert_eq!(decoder.push('x'), Key("x".to_string()));
+    assert_eq!(decoder.flush(), Pending);
+    decoder.push('\u{1b}');
+    assert_eq!(decoder.flush(), Key("ESC".to_string()));
+}
+
+#[test]
+fn key_decoder_paste_test() {
+    let mut decoder = KeyDecoder::new(15);
+    let mut decoded:Vec<Decoded> = "\u{1b}[200~a\u{1b}[Ab\n\u{1b}[201~c".chars().map(|c| decoder.push(c)).collect();
+    decoded.retain(|d| *d != Pending);
+    assert_eq!(decoded, vec![Paste("a\u{1b}[Ab\n".to_string()), Key("c".to_string())]);
+}
@@ -0,0 +1,84 @@
+// The kill ring: text killed in the line editor, newest first, so it
+// can be yanked back and cycled through like in emacs
+use std::collections::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum KillMerge {
+    // a kill on its own
+    New,
+    // following another kill, forward or backward
+    Append,
+    Prepend
+}
+
+pub struct KillRing {
+    entries: VecDeque<String>,
+    size: usize,
+    // entry last yanked, moved on by rotate
+    yank: usize
+}
+
+impl KillRing {
+    pub fn new(size:usize) -> KillRing {
+        KillRing {
+            entries: VecDeque::new(),
+            size: size,
+            yank: 0
+        }
+    }
+
+    pub fn kill(&mut self, text:String, merge:KillMerge) {
+        if text.is_empty() {
+            return;
+        }
+        self.yank = 0;
+        match (merge, self.entries.front_mut()) {
+            (KillMerge::Append, Some(last)) => {
+                last.push_str(text.as_slice());
+                return;
+            },
+            (KillMerge::Prepend, Some(last)) => {
+                *last = format!("{}{}", text, last);
+                return;
+            },
+            _ => {}
+        }
+        self.entries.push_front(text);
+        while self.entries.len() > self.size {
+            self.entries.pop_back();
+        }
+    }
+
+    // the newest kill
+    pub fn yank(&mut self) -> Option<String> {
+        self.yank = 0;
+        self.entries.front().map(|s| s.clone())
+    }
+
+    // the kill before the one last yanked, going around to the newest
+    pub fn rotate(&mut self) -> Option<String> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.yank = (self.yank + 1) % self.entries.len();
+        self.entries.get(self.yank).map(|s| s.clone())
+    }
+}
+
+#[test]
+fn kill_ring_test() {
+    let mut ring = KillRing::new(2);
+    assert_eq!(ring.yank(), None);
+    ring.kill("one".to_string(), KillMerge::New);
+    ring.kill(" more".to_string(), KillMerge::Append);
+    ring.kill("two".to_string(), KillMerge::New);
+    ring.kill("a ".to_string(), KillMerge::Prepend);
+    assert_eq!(ring.yank(), Some("a two".to_string()));
+    assert_eq!(ring.rotate(), Some("one more".to_string()));
+    assert_eq!(ring.rotate(), Some("a two".to_string()));
+    // only the newest are kept
+    ring.kill("three".to_string(), KillMerge::New);
+    assert_eq!(ring.yank(), Some("three".to_string()));
+    assert_eq!(ring.rotate(), Some("a two".to_string()));
+    assert_eq!(ring.rotate(), Some("three".to_string()));
+}
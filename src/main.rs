@@ -16,6 +16,7 @@
 #![feature(unicode)]
 #![feature(rustc_private)]
 #![feature(plugin)]
+#![feature(exit_status)]
 #![plugin(regex_macros)]
 extern crate sodiumoxide;
 extern crate libc;
@@ -33,10 +34,16 @@ use types::*;
 use ast::*;
 use env::*;
 use handlers::*;
+use source::*;
+use history::*;
+use ioctl::*;
+use error::*;
+use prompt::*;
 
-use types::InputValue::*;
+use std::path::Path;
 
 mod constants;
+mod termconst;
 #[macro_use]
 mod util;
 mod termios;
@@ -52,19 +59,122 @@ mod env;
 mod ioctl;
 mod ast;
 mod handlers;
+mod source;
+mod settings;
+mod history;
+mod completion;
+mod fuzzy;
+mod graph;
+mod bash;
+mod keys;
+mod bindings;
+mod vi;
+mod killring;
+mod undo;
+mod highlight;
+mod prompt;
+mod error;
 
 // public so no warnings when we run tests
 pub fn main() {
-    let mut reader = LineReader::new();
+    let args:Vec<String> = std::env::args().collect();
     let mut env = WashEnv::new();
     let mut ast = AST::new();
-    let mut cleaned_jobs;
     match load_builtins(&mut env) {
         Err(e) => env.errf(format_args!("Could not load builtings: {}\n", e)),
         _ => {}
     }
     load_handlers(&mut ast);
+    if args.len() > 1 && args[1] == "-c" {
+        // wash -c "command line" args...
+        if args.len() < 3 {
+            env.errf(format_args!("wash: -c: option requires an argument\n"));
+            std::env::set_exit_status(2);
+            return;
+        }
+        env.args = vec![args[0].clone()];
+        env.args.push_all(&args[3..]);
+        env.term.interactive = false;
+        env.update_terminal();
+        let status = run_string("-c", args[2].as_slice(), &mut ast, &mut env);
+        std::env::set_exit_status(status);
+    } else if args.len() > 1 {
+        // wash path/to/file.wash args...
+        // $sys:args starts with the script, like argv does with wash
+        env.args = args[1..].to_vec();
+        env.term.interactive = false;
+        env.update_terminal();
+        let status = run_file(Path::new(args[1].as_slice()), &mut ast, &mut env);
+        std::env::set_exit_status(status);
+    } else if !env.term.interactive {
+        // stdin or stdout is a pipe or file
+        env.update_terminal();
+        let status = run_piped(&mut ast, &mut env);
+        std::env::set_exit_status(status);
+    } else {
+        run_interactive(&mut ast, &mut env);
+        std::env::set_exit_status(env.final_status());
+    }
+}
+
+fn run_piped(ast:&mut AST, env:&mut WashEnv) -> i32 {
+    let mut reader = PlainReader::new();
+    let mut source = SourceReader::new("stdin");
+    loop {
+        match reader.read_line() {
+            None => break,
+            Some(line) => source.feed_str(line.as_slice(), ast, env)
+        }
+        env.flush();
+    }
+    source.finish(ast, env);
+    env.flush();
+    return env.final_status();
+}
+
+// prompts can run commands too, which shouldn't change $sys:status
+fn run_keeping_status(name:&str, env:&mut WashEnv) -> WashResult<WashArgs> {
+    let status = env.last_exit.clone();
+    let out = env.runf(&name.to_string(), &WashArgs::Empty);
+    env.set_exit(status);
+    return out;
+}
+
+fn run_prompt(ast:&AST, env:&mut WashEnv) -> String {
+    let name = if ast.in_block() {"subprompt"} else {"prompt"};
+    match run_keeping_status(name, env) {
+        Err(_) => format!("prompt failed => run("),
+        Ok(v) => render_prompt(v.flatten().as_slice())
+    }
+}
+
+fn run_rprompt(ast:&AST, env:&mut WashEnv) -> String {
+    if ast.in_block() {
+        return String::new();
+    }
+    match run_keeping_status("rprompt", env) {
+        Err(_) => String::new(),
+        Ok(v) => render_prompt(v.flatten().as_slice())
+    }
+}
+
+fn run_interactive(ast:&mut AST, env:&mut WashEnv) {
+    let mut reader = LineReader::new();
+    reader.handlers = ast.handler_names();
+    let mut cleaned_jobs;
     env.update_terminal();
+    load_rc(ast, env);
+    for name in env.settings.take_changed().iter() {
+        reader.setting_changed(name, &env.settings);
+    }
+    match history_path(env) {
+        None => {},
+        Some(path) => match load_history(path.as_path()) {
+            // no history yet is fine
+            Err(_) => {},
+            Ok(entries) => reader.load_history(&entries)
+        }
+    }
     loop {
         env.flush();
         cleaned_jobs = env.clean_jobs();
@@ -76,18 +186,24 @@ pub fn main() {
             },
             _ => {/* nothing */}
         }
-        if ast.in_block() {
-            match env.runf(&format!("subprompt"), &WashArgs::Empty) {
-                Err(_) => reader.controls.outs("prompt failed => run("),
-                Ok(v) => reader.controls.outs(v.flatten().as_slice())
-            }
-        } else {
-            match env.runf(&format!("prompt"), &WashArgs::Empty) {
-                Err(_) => reader.controls.outs("prompt failed => run("),
-                Ok(v) => reader.controls.outs(v.flatten().as_slice())
-            }
+        for name in env.settings.take_changed().iter() {
+            reader.setting_changed(name, &env.settings);
         }
-        match reader.read_line() {
+        env.edit_mode = reader.mode.name().to_string();
+        reader.prompt = run_prompt(ast, env);
+        reader.rprompt = run_rprompt(ast, env);
+        reader.draw_prompt();
+        let mut result = reader.read_line(env);
+        while reader.mode_changed {
+            // the prompt might show the mode, so run it again
+            reader.mode_changed = false;
+            env.edit_mode = reader.mode.name().to_string();
+            let prompt = run_prompt(ast, env);
+            let rprompt = run_rprompt(ast, env);
+            reader.redraw_prompt(prompt, rprompt);
+            result = reader.read_line(env);
+        }
+        match result {
             None => {
                 if reader.eof {
                     break;
@@ -99,46 +215,32 @@ pub fn main() {
                     reader.clear();
                 }
             },
-            Some(mut line) => {
+            Some(_) => {
                 env.outc(NL);
-                match ast.add_line(&mut line) {
-                    Err(ref e) if *e == STOP => {
-                        // the silent error
-                        ast.clear();
-                    },
-                    Err(e) => {
-                        println!("Error: {}", e);
-                        ast.clear();
-                    },
-                    Ok(_) => {
-                        if !ast.in_block() {
-                            //println!("{:?}", ast);
-                            match ast.optimize() {
-                                Err(e) => {
-                                    println!("Optimization error: {}", e);
-                                },
-                                Ok(_) => {
-                                    //println!("{:?}", ast);
-                                }
-                            }
-                            match ast.into_runner().evaluate(&WashArgs::Empty, &mut env) {
-                                Err(ref e) if *e == STOP => {
-                                    // the silent error
-                                }
-                                Err(e) => {
-                                    println!("Error: {}", e);
-                                },
-                                Ok(WashArgs::Empty) => {
-                                    // print nothing
-                                },
-                                Ok(v) => {
-                                    println!("{}", v.flatten());
-                                }
-                            };
+                // a paste is run a line at a time, the same as typing it
+                for line in reader.take_lines().iter() {
+                    // saved before it runs, in case it never finishes
+                    let entry = begin_history(line.text(), env);
+                    match run_input(line, ast, env) {
+                        None => {/* still in a block */},
+                        Some(result) => print_result(&result, env)
+                    }
+                    match entry {
+                        None => {},
+                        Some(entry) => {
+                            let entry = end_history(entry, env);
+                            reader.completer.graph.record(&entry);
                         }
                     }
+                    if env.exit.is_some() {
+                        break;
+                    }
                 }
+                reader.completer.bash.line_ran();
                 reader.clear();
+                if env.exit.is_some() {
+                    break;
+                }
             }
         }
     }
@@ -0,0 +1,105 @@
+// Prompt markup: what prompt and rprompt return can ask for colors and
+// styles with %{name}, which are turned into escape sequences here
+use constants::*;
+use util::*;
+
+// names that can go in %{...}, and their select graphic rendition codes
+const STYLE_NAMES:[(&'static str, &'static str); 21] = [
+    ("reset", "0"), ("bold", "1"), ("dim", "2"), ("italic", "3"),
+    ("underline", "4"), ("blink", "5"), ("reverse", "7"),
+    ("black", "30"), ("red", "31"), ("green", "32"), ("yellow", "33"),
+    ("blue", "34"), ("magenta", "35"), ("cyan", "36"), ("white", "37"),
+    ("bg-black", "40"), ("bg-red", "41"), ("bg-green", "42"), ("bg-yellow", "43"),
+    ("bg-blue", "44"), ("bg-white", "47")
+];
+
+fn style_code(name:&str) -> Option<String> {
+    for &(n, code) in STYLE_NAMES.iter() {
+        if n == name {
+            return Some(code.to_string());
+        }
+    }
+    if !name.is_empty() && name.chars().all(|c| c.is_digit(10)) {
+        // a code given as is, for anything without a name
+        return Some(name.to_string());
+    }
+    return None;
+}
+
+// turn prompt markup into what's written to the terminal:
+// %{bold;green} sets the style from names or codes joined with ;,
+// %n is a newline and %% is %. Anything else is left alone, and the
+// style is reset at the end so it doesn't run into the line
+pub fn render_prompt(s:&str) -> String {
+    let mut out = String::new();
+    let mut styled = false;
+    let mut rest = s;
+    loop {
+        let i = match rest.find('%') {
+            None => break,
+            Some(i) => i
+        };
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if rest.starts_with("%%") {
+            out.push('%');
+            rest = &rest[2..];
+        } else if rest.starts_with("%n") {
+            out.push(NL);
+            rest = &rest[2..];
+        } else if rest.starts_with("%{") && rest.contains("}") {
+            let end = rest.find('}').unwrap();
+            let codes:Vec<Option<String>> = rest[2..end].split(';').map(style_code).collect();
+            if codes.iter().all(|c| c.is_some()) {
+                let codes:Vec<String> = codes.into_iter().map(|c| c.unwrap()).collect();
+                out.push_str(format!("{}{}m", ANSI_BEGIN, codes.connect(";")).as_slice());
+                styled = true;
+            } else {
+                // not markup after all
+                out.push_str(&rest[..end + 1]);
+            }
+            rest = &rest[end + 1..];
+        } else {
+            out.push('%');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    if styled {
+        out.push_str(format!("{}{}m", ANSI_BEGIN, STYLE_RESET).as_slice());
+    }
+    return out;
+}
+
+// columns taken by the last line of a prompt, where the line starts
+pub fn prompt_width(prompt:&str) -> usize {
+    match prompt.rfind(NL) {
+        None => visible_width(prompt),
+        Some(i) => visible_width(&prompt[i + 1..])
+    }
+}
+
+// lines above the one the line being edited starts on
+pub fn prompt_rows(prompt:&str) -> usize {
+    prompt.chars().filter(|c| *c == NL).count()
+}
+
+#[test]
+fn render_prompt_test() {
+    assert_eq!(render_prompt("wash => $("), "wash => $(");
+    assert_eq!(render_prompt("%{bold;green}ok%{reset} 100%%"),
+               "\u{1b}[1;32mok\u{1b}[0m 100%\u{1b}[0m");
+    assert_eq!(render_prompt("%{38;5;208}x"), "\u{1b}[38;5;208mx\u{1b}[0m");
+    assert_eq!(render_prompt("~%n$ "), "~\n$ ");
+    // not markup, so left as it was
+    assert_eq!(render_prompt("%{purple} 5% %{"), "%{purple} 5% %{");
+}
+
+#[test]
+fn prompt_width_test() {
+    let prompt = render_prompt("%{blue}~/src%n%{green}=> $(");
+    assert_eq!(prompt_width(prompt.as_slice()), 5);
+    assert_eq!(prompt_rows(prompt.as_slice()), 1);
+    assert_eq!(prompt_width("日本 $ "), 7);
+    assert_eq!(prompt_rows("$ "), 0);
+}
@@ -1,7 +1,10 @@
 use libc::*;
 
 use std::collections::*;
-use std::num::*;
+use std::cmp::{min, max};
+use std::io::{self, BufRead};
+use std::mem;
+use std::env;
 
 use input::*;
 use controls::*;
@@ -9,19 +12,149 @@ use constants::*;
 use signal::*;
 use types::*;
 use ioctl::*;
+use settings::*;
+use history::*;
+use completion::*;
+use fuzzy::*;
+use keys::*;
+use bindings::*;
+use bindings::KeyLookup::*;
+use vi::*;
+use vi::ViCommand::*;
+use vi::ViInput::*;
+use vi::ViOperator::*;
+use vi::InsertAt::*;
+use killring::*;
+use undo::*;
+use highlight::*;
+use prompt::*;
+use graph::*;
+use util::*;
+use env::*;
+
+// actions that kill text, so one right after another adds to the same kill
+const KILL_ACTIONS:[&'static str; 4] = ["kill-line", "kill-word", "backward-kill-word",
+                                        "unix-line-discard"];
+
+// what undo goes back to: the line being edited and the lines of a paste
+// above it
+#[derive(Clone)]
+struct EditState {
+    pasted: Vec<InputLine>,
+    line: InputLine
+}
+
+impl EditState {
+    fn text(&self) -> String {
+        let mut out = String::new();
+        for line in self.pasted.iter() {
+            out.push_str(line.text().as_slice());
+            out.push(NL);
+        }
+        out.push_str(self.line.text().as_slice());
+        return out;
+    }
+}
+
+// Reads lines from stdin when it isn't a terminal, so none of the
+// cursor tracking or escape handling LineReader does is needed
+pub struct PlainReader {
+    stdin: io::BufReader<io::Stdin>,
+    pub eof: bool
+}
+
+impl PlainReader {
+    pub fn new() -> PlainReader {
+        PlainReader {
+            stdin: io::BufReader::new(io::stdin()),
+            eof: false
+        }
+    }
+
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = String::new();
+        match self.stdin.read_line(&mut line) {
+            Ok(0) | Err(_) => {
+                self.eof = true;
+                return None;
+            },
+            Ok(_) => return Some(line)
+        }
+    }
+}
+
+// state for C-r, searching back through history
+struct SearchState {
+    query: String,
+    // history indices of lines matching query, best first
+    matches: Vec<(usize, FuzzyMatch)>,
+    // the one being shown
+    current: usize,
+    // the line from before the search, put back if it's cancelled
+    original: InputLine,
+    // columns of the search line on screen
+    drawn: usize
+}
 
 pub struct LineReader {
     pub line: InputLine,
     pub controls: Controls,
     pub bpart: String,
-    pub escape: bool,
-    pub escape_chars: String,
+    // turns what the terminal sends into keys
+    decoder: KeyDecoder,
+    // ESC typed on its own in emacs mode, which holds alt for the next key
+    meta: bool,
+    // lines of a paste before the one being edited, each run after it's
+    // accepted as if it had been typed
+    pasted: Vec<InputLine>,
     pub finished: bool,
     pub eof: bool,
     pub restarted: bool,
     history: VecDeque<InputLine>,
     bhistory: Vec<InputLine>,
-    first_rsave: bool
+    first_rsave: bool,
+    // number of lines read so far
+    lines: usize,
+    // from $cfg:history_size and $cfg:escape_timeout
+    history_size: usize,
+    escape_timeout: usize,
+    search: Option<SearchState>,
+    pub completer: Completer,
+    // editing actions keys can be bound to, and keys typed so far
+    // that start a longer binding
+    actions: HashMap<String, EditAction>,
+    keys: Vec<String>,
+    // the prompt the current line was started with, redrawn after listing completions,
+    // and the one shown against the right edge. Both are already rendered
+    pub prompt: String,
+    pub rprompt: String,
+    // emacs, or vi's insert or normal mode. mode_changed stops
+    // read_line so the prompt can be run again to show the new mode
+    pub mode: EditMode,
+    pub mode_changed: bool,
+    vi: bool,
+    vi_parser: ViParser,
+    // text deleted or yanked in normal mode, for p
+    register: String,
+    // edits from before each change, and from before each undo.
+    // undone is set while the change being made is an undo or redo
+    undo: UndoLog<EditState>,
+    undone: bool,
+    kill_ring: KillRing,
+    // the action the last key ran, so kills in a row go together
+    // and yank-pop knows it follows a yank
+    last_action: String,
+    // characters put in by the last yank, taken out again by yank-pop
+    yanked: usize,
+    // from $cfg:highlight and $cfg:colors
+    highlight: bool,
+    colors: HashMap<HighlightKind, String>,
+    // handler words like if! and |, which are highlighted
+    pub handlers: Vec<String>,
+    // the rest of a line from history that starts with this one,
+    // shown dimmed after the end of the line, and $cfg:autosuggest
+    suggestion: String,
+    autosuggest: bool
 }
 
 impl LineReader {
@@ -30,26 +163,103 @@ impl LineReader {
             line: InputLine::new(),
             controls: Controls::new(),
             bpart: String::new(),
-            escape: false,
-            escape_chars: String::new(),
+            decoder: KeyDecoder::new(MAX_ESCAPE),
+            meta: false,
+            pasted: vec![],
             finished: false,
             eof: false,
             restarted: false,
             history: VecDeque::new(),
             bhistory: vec![],
-            first_rsave: false
+            first_rsave: false,
+            lines: 0,
+            history_size: HISTORY_SIZE,
+            escape_timeout: ESCAPE_TIMEOUT,
+            search: None,
+            completer: Completer::new(),
+            actions: edit_actions(),
+            keys: vec![],
+            prompt: String::new(),
+            rprompt: String::new(),
+            mode: EditMode::Emacs,
+            mode_changed: false,
+            vi: false,
+            vi_parser: ViParser::new(),
+            register: String::new(),
+            undo: UndoLog::new(),
+            undone: false,
+            kill_ring: KillRing::new(KILL_RING_SIZE),
+            last_action: String::new(),
+            yanked: 0,
+            highlight: true,
+            colors: parse_colors(DEFAULT_COLORS).unwrap(),
+            handlers: vec![],
+            suggestion: String::new(),
+            autosuggest: true
+        }
+    }
+
+    pub fn setting_changed(&mut self, name:&String, settings:&Settings) {
+        if *name == "history_size" {
+            self.history_size = settings.number("history_size");
+            while self.history.len() > self.history_size {
+                self.history.pop_back();
+            }
+        } else if *name == "max_escape" {
+            self.decoder.max = settings.number("max_escape");
+        } else if *name == "escape_timeout" {
+            self.escape_timeout = settings.number("escape_timeout");
+        } else if *name == "bash_completion" {
+            // replies from the old scripts are no good
+            self.completer.bash.clear();
+        } else if *name == "highlight" {
+            self.highlight = settings.flag("highlight");
+        } else if *name == "autosuggest" {
+            self.autosuggest = settings.flag("autosuggest");
+        } else if *name == "colors" {
+            match parse_colors(settings.text("colors").as_slice()) {
+                Err(_) => {/* checked when it was set */},
+                Ok(colors) => self.colors = colors
+            }
+        } else if *name == "editing_mode" {
+            self.vi = settings.text("editing_mode") == "vi";
+            self.mode = if self.vi {EditMode::Insert} else {EditMode::Emacs};
+        }
+    }
+
+    // entries are given oldest first
+    pub fn load_history(&mut self, entries:&Vec<HistoryEntry>) {
+        for entry in entries.iter() {
+            self.completer.graph.record(entry);
+            match entry.to_input() {
+                None => {/* not a line we could have read */},
+                Some(line) => self.history.push_front(line)
+            }
+        }
+        while self.history.len() > self.history_size {
+            self.history.pop_back();
         }
     }
 
     pub fn clear(&mut self) {
         self.line.clear();
         self.bpart.clear();
-        self.escape = false;
-        self.escape_chars.clear();
+        self.decoder.reset();
+        self.meta = false;
+        self.pasted.clear();
         self.finished = false;
         self.eof = false;
         self.restarted = false;
         self.first_rsave = false;
+        self.keys.clear();
+        // every line starts out in insert mode
+        if self.vi {
+            self.mode = EditMode::Insert;
+        }
+        self.mode_changed = false;
+        self.vi_parser.reset();
+        self.undo.clear();
+        self.last_action.clear();
     }
 
     pub fn restart(&mut self) {
@@ -65,7 +275,10 @@ impl LineReader {
         };
         match sig.signo {
             SIGINT => {
-                self.controls.cursors_right(self.line.part.len());
+                self.erase_suggestion();
+                self.suggestion.clear();
+                self.search = None;
+                self.controls.cursors_right(str_width(self.line.part.as_slice()));
                 self.controls.outs("\nInterrupt");
                 self.clear();
                 self.finished = true;
@@ -75,30 +288,251 @@ impl LineReader {
                     Err(e) => self.controls.errf(format_args!("\nCouldn't get terminal size: {}\n", e)),
                     Ok(size) => self.controls.update_size(size)
                 }
+                // rprompt was drawn against the old right edge
+                let after = str_width(self.line.part.as_slice()) + str_width(self.suggestion.as_slice());
+                self.controls.clear_after(after);
+                self.draw_rprompt();
                 self.controls.query_cursor();
             },
             s => panic!("Caught bad signal: {}", s)
         }
     }
 
-    fn read_character(&mut self) {
+    fn read_character(&mut self, env:&WashEnv) {
         match self.controls.read() {
             Err(e) => panic!("Error: {}", e),
-            Ok(ch) => match
-                if self.escape {
-                    self.handle_escape(ch)
-                } else if ch.is_control() {
-                    self.handle_control(ch)
-                } else {
-                    self.handle_ch(ch)
-                } {
-                    false => self.controls.bell(),
-                    _ => {}
+            Ok(ch) => {
+                let decoded = self.decoder.push(ch);
+                self.handle_decoded(decoded, env);
+            }
+        }
+    }
+
+    // nothing came after the start of an escape sequence in time,
+    // so it was only ESC or an alt key
+    fn flush_escape(&mut self, env:&WashEnv) {
+        let decoded = self.decoder.flush();
+        self.handle_decoded(decoded, env);
+    }
+
+    fn handle_decoded(&mut self, decoded:Decoded, env:&WashEnv) {
+        let before = self.edit_state();
+        self.undone = false;
+        // it's drawn again after, if there still is one
+        self.erase_suggestion();
+        let done = match decoded {
+            Decoded::Pending => true,
+            Decoded::Key(key) => self.handle_input(key, env),
+            Decoded::CursorReport(row, col) => self.cursor_report(row, col),
+            Decoded::Paste(text) => self.paste(text),
+            Decoded::Unknown(_) => false
+        };
+        if !done {
+            self.controls.bell();
+        }
+        if self.edit_state().text() != before.text() {
+            if !self.undone {
+                self.record_change(before);
+            }
+            self.highlight_line(env);
+        } else if self.last_action != "self-insert" {
+            // moving around ends a run of typing
+            self.undo.end_group();
+        }
+        self.show_suggestion();
+        self.draw_rprompt();
+    }
+
+    // the rest of the newest line run in this directory that starts with
+    // this one, or of any line in history if there isn't one
+    fn find_suggestion(&self) -> Option<String> {
+        let text = self.line.text();
+        if text.trim().is_empty() {
+            return None;
+        }
+        let rest = |line:&String| {
+            if line.len() > text.len() && line.starts_with(text.as_slice()) &&
+                !line.contains(NL.to_string().as_slice()) {
+                Some(line[text.len()..].to_string())
+            } else {
+                None
+            }
+        };
+        let graph = &self.completer.graph;
+        let dir = match env::current_dir() {
+            Err(_) => None,
+            Ok(p) => graph.find(NodeKind::Directory, format!("{}", p.display()).as_slice())
+        };
+        match dir {
+            None => {},
+            Some(dir) => {
+                let found = graph.complete(dir, NodeKind::History, &Completion::MinComplete(time_weight),
+                                           get_time_ms(), text.as_slice());
+                for id in found.iter() {
+                    match rest(&graph.get(*id).text) {
+                        None => {},
+                        Some(s) => return Some(s)
+                    }
+                }
+            }
+        }
+        for line in self.history.iter() {
+            match rest(&line.text()) {
+                None => {},
+                Some(s) => return Some(s)
+            }
+        }
+        return None;
+    }
+
+    fn show_suggestion(&mut self) {
+        self.suggestion.clear();
+        // only at the end of the line being edited
+        if !self.autosuggest || self.finished || self.eof || self.mode_changed ||
+            self.search.is_some() || self.mode == EditMode::Normal || !self.line.part.is_empty() {
+            return;
+        }
+        let suggestion = match self.find_suggestion() {
+            None => return,
+            Some(s) => s
+        };
+        let code = match self.colors.get(&HighlightKind::Suggestion) {
+            None => String::new(),
+            Some(c) => c.clone()
+        };
+        if !code.is_empty() {
+            self.controls.style(code.as_slice());
+        }
+        let count = self.draw_text(suggestion.as_slice());
+        if !code.is_empty() {
+            self.controls.style(STYLE_RESET);
+        }
+        self.controls.cursors_left(count);
+        self.suggestion = suggestion;
+    }
+
+    // take the suggestion off the screen, it's still kept until the next one
+    fn erase_suggestion(&mut self) {
+        if !self.suggestion.is_empty() {
+            self.controls.clear_line_to(str_width(self.suggestion.as_slice()));
+        }
+    }
+
+    // type in all of the suggestion, or up to the end of its next word
+    fn accept_suggestion(&mut self, whole:bool) -> bool {
+        if self.suggestion.is_empty() || !self.line.part.is_empty() {
+            return false;
+        }
+        let text:Vec<char> = format!("{}{}", self.line.text(), self.suggestion).chars().collect();
+        let pos = self.cursor();
+        let end = if whole {text.len()} else {word_forward(&text, pos)};
+        let taken:String = text[pos..end].iter().map(|c| *c).collect();
+        return self.insert_str(taken.as_slice());
+    }
+
+    // draw the line again in color, over what was typed
+    fn highlight_line(&mut self, env:&WashEnv) {
+        if !self.highlight || self.search.is_some() {
+            return;
+        }
+        let text:Vec<char> = self.line.text().chars().collect();
+        let mut spans = highlight(&text, &self.handlers);
+        check_commands(&mut spans, &text, env);
+        self.controls.cursors_left(str_width(self.line.fpart.as_slice()));
+        let mut at = 0;
+        for span in spans.iter() {
+            self.draw_chars(&text[at..span.start]);
+            let code = match self.colors.get(&span.kind) {
+                None => String::new(),
+                Some(c) => c.clone()
+            };
+            if !code.is_empty() {
+                self.controls.style(code.as_slice());
+            }
+            self.draw_chars(&text[span.start..span.end]);
+            if !code.is_empty() {
+                self.controls.style(STYLE_RESET);
+            }
+            at = span.end;
+        }
+        self.draw_chars(&text[at..]);
+        self.controls.cursors_left(str_width(self.line.part.as_slice()));
+    }
+
+    fn draw_chars(&mut self, chars:&[char]) {
+        if !chars.is_empty() {
+            let s:String = chars.iter().map(|c| *c).collect();
+            self.controls.outs(s.as_slice());
+        }
+    }
+
+    fn handle_input(&mut self, key:String, env:&WashEnv) -> bool {
+        if self.search.is_some() {
+            return self.handle_search(key, env);
+        }
+        if self.mode == EditMode::Emacs {
+            if self.meta {
+                self.meta = false;
+                return self.handle_key(add_meta(key), env);
+            }
+            match env.keymap.lookup(&key) {
+                Unbound if key == "ESC" && self.keys.is_empty() => {
+                    self.meta = true;
+                    return true;
+                },
+                _ => {}
+            }
+        }
+        if self.mode != EditMode::Emacs {
+            if key == "ESC" {
+                // leaves insert mode, or forgets a half typed command
+                self.enter_normal();
+                self.vi_parser.reset();
+                return true;
+            } else if key.starts_with("M-") {
+                // ESC typed quickly before a key
+                self.enter_normal();
+                return self.handle_input(key[2..].to_string(), env);
+            }
+        }
+        if self.mode == EditMode::Normal {
+            match key_char(key.as_slice()) {
+                None => {},
+                Some(ch) => return self.handle_vi(ch)
+            }
+        }
+        return self.handle_key(key, env);
+    }
+
+    // run whatever the keys typed so far are bound to
+    fn handle_key(&mut self, key:String, env:&WashEnv) -> bool {
+        self.keys.push(key);
+        let sequence = self.keys.connect(" ");
+        match env.keymap.lookup(&sequence) {
+            Prefix => return true,
+            Bound(name) => {
+                self.keys.clear();
+                let action = match self.actions.get(&name) {
+                    None => return false,
+                    Some(a) => *a
+                };
+                let out = action(self, env);
+                self.last_action = name;
+                return out;
+            },
+            Unbound => {
+                self.keys.clear();
+                self.last_action = "self-insert".to_string();
+                // keys that print type themselves
+                match key_char(sequence.as_slice()) {
+                    None => return false,
+                    Some(ch) => return self.handle_ch(ch)
                 }
+            }
         }
     }
 
-    pub fn read_line(&mut self) -> Option<InputValue> {
+    pub fn read_line(&mut self, env:&WashEnv) -> Option<InputValue> {
         // these panic because if we can't do this we can't run wash at all
         let mut set = tryp!(empty_sigset());
         tryp!(sigset_add(&mut set, SIGINT));
@@ -116,13 +550,18 @@ impl LineReader {
         // update cursor position before anything
         self.controls.clear_rows();
         self.controls.query_cursor();
-        while !self.finished && !self.eof {
+        self.controls.bracketed_paste(true);
+        while !self.finished && !self.eof && !self.mode_changed {
+            // wait a little for the rest of an escape sequence
+            let timeout = if self.decoder.pending() {Some(self.escape_timeout)} else {None};
             sread = match select(&read, &emvc, &emvc,
-                                 None, &set) {
+                                 timeout, &set) {
                 Err(_) => continue, // try again
                 Ok(v) => v
             };
-            if sread.len() == 2 {
+            if sread.is_empty() && timeout.is_some() {
+                self.flush_escape(env);
+            } else if sread.len() == 2 {
                 // prefer SIGINT
                 self.handle_signal(&set);
             } else {
@@ -131,14 +570,17 @@ impl LineReader {
                     Some(ref fd) if *fd == sigfd =>
                         self.handle_signal(&set),
                     Some(ref fd) if *fd == STDIN =>
-                        self.read_character(),
+                        self.read_character(env),
                     _ => panic!("select returned unknown file descriptor")
                 }
             }
             self.controls.flush();
         }
+        // commands run from here don't expect pastes to be marked
+        self.controls.bracketed_paste(false);
+        self.controls.flush();
         tryp!(signal_proc_mask(SIG_SETMASK, &old_set));
-        if self.eof {
+        if self.eof || self.mode_changed {
             return None;
         } else {
             // push back history onto history
@@ -152,10 +594,17 @@ impl LineReader {
                     self.history.push_front(popped);
                 }
             }
+            for line in self.pasted.iter_mut() {
+                self.lines += 1;
+                line.pos = SourcePos::new("input", self.lines);
+                self.history.push_front(line.clone());
+            }
             self.history.push_front(self.line.clone());
-            while self.history.len() > HISTORY_SIZE {
+            while self.history.len() > self.history_size {
                 self.history.pop_back();
             }
+            self.lines += 1;
+            self.line.pos = SourcePos::new("input", self.lines);
             return self.line.process();
         }        
     }
@@ -175,13 +624,20 @@ impl LineReader {
                 }
             }
         }
-        let splits:Vec<&str> = NL_REGEX.split(self.bpart.as_slice()).collect();
-        if self.controls.grow_check(splits[0].len()) {
+        let bpart = self.bpart.clone();
+        return self.draw_text(bpart.as_slice());
+    }
+
+    // draw text after the cursor, clearing what's below it if it
+    // wraps, and leave the cursor at its end
+    fn draw_text(&mut self, text:&str) -> usize {
+        let splits:Vec<&str> = NL_REGEX.split(text).collect();
+        if self.controls.grow_check(str_width(splits[0])) {
             let old = self.controls.get_pos();
             for part in splits.iter() {
-                if self.controls.grow_check(part.len()) {
+                if self.controls.grow_check(str_width(part)) {
                     let crow = self.controls.get_row();
-                    let total = crow + part.len();
+                    let total = crow + str_width(part);
                     for row in range(crow + 1,
                                      crow + total/self.controls.width() + 1) {
                         self.controls.clear_line();
@@ -198,12 +654,13 @@ impl LineReader {
                 }
             }
             self.controls.move_to(old);
-            self.controls.outs(self.bpart.as_slice());
-            return self.bpart.len();
+            self.controls.outs(text);
+            // each newline counts as one step back
+            return str_width(text) + splits.len() - 1;
         } else {
             // change doesn't affect anything other than this line
             self.controls.outs(splits[0]);
-            return splits[0].len();
+            return str_width(splits[0]);
         }
     }
 
@@ -223,177 +680,775 @@ impl LineReader {
         }
     }
 
-    pub fn handle_control(&mut self, ch:char) -> bool {
-        match ch {
-            CEOF => {
-                if self.line.is_empty() {
-                    self.finished = true;
-                    self.eof = true;
-                }
+    pub fn end_of_file(&mut self) -> bool {
+        if self.line.is_empty() {
+            self.finished = true;
+            self.eof = true;
+        }
+        return true;
+    }
+
+    pub fn accept_line(&mut self) -> bool {
+        if !self.line.push(NL) {
+            self.finished = true;
+        } else {
+            // the line isn't finished, keep going on the next one
+            self.controls.outc(NL);
+        }
+        return true;
+    }
+
+    pub fn beginning_of_line(&mut self) -> bool {
+        self.controls.cursors_left(str_width(self.line.fpart.as_slice()));
+        loop {
+            match self.line.fpart.pop() {
+                Some(ch) => self.line.part.push(ch),
+                None => break
+            }
+        }
+        self.line.fpart.clear();
+        self.line.back.clear();
+        self.line.front.clear();
+        self.line.back.push(InputValue::Long(vec![]));
+        self.bpart.clear();
+        return true;
+    }
+
+    pub fn end_of_line(&mut self) -> bool {
+        if self.line.part.is_empty() && !self.suggestion.is_empty() {
+            return self.accept_suggestion(true);
+        }
+        self.controls.cursors_right(str_width(self.line.part.as_slice()));
+        while self.line.right() {}
+        return true;
+    }
+
+    pub fn backward_char(&mut self) -> bool {
+        if self.line.left() {
+            self.bpart.clear();
+            let width = self.line.part.chars().last().map_or(0, char_width);
+            self.controls.cursors_left(width);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    pub fn forward_char(&mut self) -> bool {
+        if self.line.part.is_empty() && !self.suggestion.is_empty() {
+            return self.accept_suggestion(true);
+        }
+        if self.line.right() {
+            self.bpart.clear();
+            let width = self.line.fpart.chars().last().map_or(0, char_width);
+            self.controls.cursors_right(width);
+            return true;
+        } else {
+            return false;
+        }
+    }
+
+    pub fn history_prev(&mut self) -> bool {
+        match self.history.pop_front() {
+            None => return false,
+            Some(line) => {
+                self.bhistory.push(self.line.clone());
+                self.replace_line(line);
+            }
+        }
+        return true;
+    }
+
+    pub fn history_next(&mut self) -> bool {
+        match self.bhistory.pop() {
+            None if !self.line.is_empty() => {
+                self.history.push_front(self.line.clone());
+                self.clear_entire_line();
             },
-            NL => {
-                if !self.line.push(NL) {
-                    self.finished = true;
-                } else {
-                    self.controls.outc(NL);
+            None => return false,
+            Some(line) => {
+                self.history.push_front(self.line.clone());
+                self.replace_line(line);
+            }
+        }
+        return true;
+    }
+
+    pub fn delete_char(&mut self) -> bool {
+        if self.line.fpart.is_empty() && !self.pasted.is_empty() {
+            return self.join_pasted();
+        }
+        match self.line.pop() {
+            None => return false,
+            Some(ch) => {
+                let width = char_width(ch);
+                self.controls.del(width);
+                let count = self.draw_part();
+                self.controls.outs(build_string(SPC, width).as_slice());
+                self.controls.del(width);
+                self.controls.cursors_left(count);
+            }
+        }
+        return true;
+    }
+
+    // the character under the cursor
+    pub fn delete_next_char(&mut self) -> bool {
+        if self.line.part.is_empty() {
+            return false;
+        }
+        return self.forward_char() && self.delete_char();
+    }
+
+    fn insert_str(&mut self, s:&str) -> bool {
+        for ch in s.chars() {
+            if !self.handle_ch(ch) {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    // put text in place of the word being completed,
+    // starting a literal if it has characters that need quoting
+    fn replace_word(&mut self, context:&CompletionContext, text:&String) -> bool {
+        let quote = !context.literal && text.chars().any(|c| [SPC, CMA, QUT, OPR, CPR, NL].contains(&c));
+        if !quote && text.starts_with(context.word.as_slice()) {
+            return self.insert_str(&text[context.word.len()..]);
+        }
+        // fuzzy matches and quoted words are typed again
+        for _ in context.word.chars() {
+            if !self.delete_char() {
+                return false;
+            }
+        }
+        if quote && !self.handle_ch(QUT) {
+            return false;
+        }
+        return self.insert_str(text.as_slice());
+    }
+
+    pub fn complete(&mut self, env:&WashEnv) -> bool {
+        let context = match completion_context(&self.line) {
+            None => return false,
+            Some(c) => c
+        };
+        let candidates = self.completer.complete(&context, env);
+        if candidates.is_empty() {
+            return false;
+        } else if candidates.len() == 1 {
+            if !self.replace_word(&context, &candidates[0].text) {
+                return false;
+            }
+            if candidates[0].complete {
+                // finish the word
+                match self.line.front {
+                    InputValue::Literal(_) => if !self.handle_ch(QUT) {
+                        return false;
+                    },
+                    _ => {}
                 }
-            },
-            ESC => {
-                self.escape = true;
-                self.escape_chars = String::new();
-            },
-            DEL => {
-                match self.line.pop() {
-                    None => return false,
-                    Some(_) => {
-                        self.controls.del();
-                        let count = self.draw_part();
-                        self.controls.outc(SPC);
-                        self.controls.del();
-                        self.controls.cursors_left(count);
+                return self.handle_ch(SPC);
+            }
+            return true;
+        }
+        let prefix = common_prefix(&candidates);
+        if prefix.len() > context.word.len() {
+            return self.replace_word(&context, &prefix);
+        }
+        self.list_candidates(&candidates);
+        return true;
+    }
+
+    // show candidates in columns under the line, then draw the line again
+    fn list_candidates(&mut self, candidates:&Vec<Candidate>) {
+        self.controls.cursors_right(str_width(self.line.part.as_slice()));
+        self.controls.outc(NL);
+        let colw = candidates.iter().map(|c| str_width(c.text.as_slice())).max().unwrap_or(0) + 2;
+        let cols = max(1, self.controls.width() / colw);
+        for (i, candidate) in candidates.iter().enumerate() {
+            self.controls.outs(candidate.text.as_slice());
+            if (i + 1) % cols == 0 || i + 1 == candidates.len() {
+                self.controls.outc(NL);
+            } else {
+                for _ in 0..(colw - str_width(candidate.text.as_slice())) {
+                    self.controls.outc(SPC);
+                }
+            }
+        }
+        self.draw_prompt();
+        self.controls.outs(self.line.fpart.as_slice());
+        self.bpart.clear();
+        self.idraw_part();
+    }
+
+    pub fn start_search(&mut self) -> bool {
+        // take the line off the screen, but keep it around
+        self.controls.cursors_left(str_width(self.line.fpart.as_slice()));
+        self.controls.clear_line_to(str_width(self.line.fpart.as_slice()) + str_width(self.line.part.as_slice()));
+        self.bpart.clear();
+        self.search = Some(SearchState {
+            query: String::new(),
+            matches: vec![],
+            current: 0,
+            original: self.line.clone(),
+            drawn: 0
+        });
+        self.draw_search();
+        return true;
+    }
+
+    // lines in history matching query, newest first
+    fn find_matches(&self, query:&String) -> Vec<(usize, FuzzyMatch)> {
+        if query.is_empty() {
+            return vec![];
+        }
+        let texts:Vec<String> = self.history.iter().map(|line| line.text()).collect();
+        return history_rank(query.as_slice(), &texts);
+    }
+
+    fn set_search(&mut self, query:String, matches:Vec<(usize, FuzzyMatch)>, current:usize) {
+        match self.search {
+            None => return,
+            Some(ref mut search) => {
+                search.query = query;
+                search.matches = matches;
+                search.current = current;
+            }
+        }
+        self.draw_search();
+    }
+
+    fn draw_search(&mut self) {
+        let (query, found, drawn) = match self.search {
+            None => return,
+            Some(ref search) => (search.query.clone(),
+                                 search.matches.get(search.current).map(|m| m.clone()),
+                                 search.drawn)
+        };
+        self.controls.cursors_left(drawn);
+        self.controls.clear_line_to(drawn);
+        let head = if found.is_none() && !query.is_empty() {
+            format!("(failed search)`{}': ", query)
+        } else {
+            format!("(search)`{}': ", query)
+        };
+        self.controls.outs(head.as_slice());
+        let mut count = str_width(head.as_slice());
+        match found {
+            None => {},
+            Some((i, m)) => {
+                // keep the match on one line, with the matched characters highlighted
+                let text = self.history[i].text().replace("\n", " ");
+                for (pos, ch) in text.chars().enumerate() {
+                    if m.positions.contains(&pos) {
+                        self.controls.style(STYLE_REVERSE);
+                        self.controls.outc(ch);
+                        self.controls.style(STYLE_RESET);
+                    } else {
+                        self.controls.outc(ch);
                     }
                 }
+                count += str_width(text.as_slice());
+            }
+        }
+        match self.search {
+            None => {},
+            Some(ref mut search) => search.drawn = count
+        }
+    }
+
+    fn end_search(&mut self, accept:bool) {
+        let search = match self.search.take() {
+            None => return,
+            Some(s) => s
+        };
+        self.controls.cursors_left(search.drawn);
+        self.controls.clear_line_to(search.drawn);
+        self.line = match search.matches.get(search.current) {
+            Some(&(i, _)) if accept => self.history[i].clone(),
+            _ => search.original
+        };
+        self.controls.outs(self.line.fpart.as_slice());
+        self.bpart.clear();
+        self.idraw_part();
+    }
+
+    pub fn handle_search(&mut self, key:String, env:&WashEnv) -> bool {
+        let (mut query, count, current) = match self.search {
+            None => return false,
+            Some(ref search) => (search.query.clone(), search.matches.len(),
+                                 search.current)
+        };
+        match key.as_slice() {
+            "C-r" => {
+                // show the next best match
+                if current + 1 >= count {
+                    return false;
+                }
+                match self.search {
+                    None => {},
+                    Some(ref mut search) => search.current += 1
+                }
+                self.draw_search();
             },
-            CTA => {
-                // C-a
-                self.controls.cursors_left(self.line.fpart.len());
-                loop {
-                    match self.line.fpart.pop() {
-                        Some(ch) => self.line.part.push(ch),
-                        None => break
-                    }
+            "C-g" | "ESC" => self.end_search(false),
+            "DEL" => {
+                if query.pop().is_none() {
+                    return false;
                 }
-                self.line.fpart.clear();
-                self.line.back.clear();
-                self.line.front.clear();
-                self.line.back.push(InputValue::Long(vec![]));
-                self.bpart.clear();
+                let found = self.find_matches(&query);
+                self.set_search(query, found, 0);
             },
-            CTE => {
-                // C-e
-                self.controls.cursors_right(self.line.part.len());
-                while self.line.right() {}
+            "RET" => {
+                // accept and run
+                self.end_search(true);
+                return self.accept_line();
             },
-            CTK => self.clear_line(),
-            _ => return false
+            _ => match key_char(key.as_slice()) {
+                None => {
+                    // other keys accept, then act on the line
+                    self.end_search(true);
+                    return self.handle_input(key, env);
+                },
+                Some(c) => {
+                    query.push(c);
+                    let found = self.find_matches(&query);
+                    let failed = found.is_empty();
+                    self.set_search(query, found, 0);
+                    if failed {
+                        return false;
+                    }
+                }
+            }
         }
         return true;
     }
 
-    fn clear_line(&mut self) {
-        self.controls.clear_line_to(self.line.part.len());
+    // put pasted text in at the cursor as one piece. Newlines that would
+    // end the line start another one instead, and all of them are run
+    // together once the last is accepted
+    pub fn paste(&mut self, text:String) -> bool {
+        self.last_action = "paste".to_string();
+        if self.search.is_some() {
+            self.end_search(true);
+        }
+        let mut all = true;
+        for u_ch in text.chars() {
+            let ch = match u_ch {
+                '\r' => NL,
+                '\t' => SPC,
+                c => c
+            };
+            if ch == NL {
+                if self.line.push(NL) {
+                    // in a literal
+                    self.controls.outc(NL);
+                    self.idraw_part();
+                } else {
+                    self.paste_line();
+                }
+            } else if self.line.is_empty() && ch == SPC {
+                // skip indentation
+            } else if !self.handle_ch(ch) {
+                all = false;
+            }
+        }
+        return all;
+    }
+
+    fn paste_line(&mut self) {
+        // what was after the cursor goes down with it
+        let rest = self.line.part.clone();
+        self.controls.clear_line_to(str_width(self.line.part.as_slice()));
         self.line.part.clear();
         self.bpart.clear();
+        let done = mem::replace(&mut self.line, InputLine::new());
+        self.pasted.push(done);
+        self.controls.outc(NL);
+        self.line.part = rest;
+        self.idraw_part();
     }
 
-    fn clear_entire_line(&mut self) {
-        self.controls.cursors_left(self.line.fpart.len());
-        self.controls.clear_line_to(self.line.fpart.len() + self.line.part.len());
+    // backspace at the start of a line after a paste goes back up to the
+    // end of the line before it, so pasted lines can still be edited
+    fn join_pasted(&mut self) -> bool {
+        let rest = self.line.part.clone();
+        self.clear_entire_line();
+        // back over the newline
+        self.controls.cursors_left(1);
+        self.line = match self.pasted.pop() {
+            None => return false,
+            Some(line) => line
+        };
+        self.line.part = rest;
         self.bpart.clear();
-        self.line.clear();
+        self.idraw_part();
+        return true;
+    }
+
+    // every line accepted, the lines of a paste first
+    pub fn take_lines(&mut self) -> Vec<InputLine> {
+        let mut out:Vec<InputLine> = self.pasted.drain().collect();
+        out.push(self.line.clone());
+        return out;
+    }
+
+    pub fn kill_line(&mut self) -> bool {
+        let killed = self.line.part.chars().rev().collect();
+        self.kill(killed, false);
+        self.controls.clear_line_to(str_width(self.line.part.as_slice()));
+        self.line.part.clear();
+        self.bpart.clear();
+        return true;
+    }
+
+    // save killed text, joined onto the last kill if that came right before
+    fn kill(&mut self, text:String, backward:bool) {
+        let merge = if !KILL_ACTIONS.contains(&self.last_action.as_slice()) {
+            KillMerge::New
+        } else if backward {
+            KillMerge::Prepend
+        } else {
+            KillMerge::Append
+        };
+        self.kill_ring.kill(text, merge);
+    }
+
+    fn kill_range(&mut self, from:usize, to:usize, backward:bool) -> bool {
+        if from == to {
+            return false;
+        }
+        let text:Vec<char> = self.line.text().chars().collect();
+        self.kill(text[from..to].iter().map(|c| *c).collect(), backward);
+        return self.delete_range(from, to);
+    }
+
+    pub fn backward_word(&mut self) -> bool {
+        let text:Vec<char> = self.line.text().chars().collect();
+        let pos = self.cursor();
+        let to = word_back(&text, pos);
+        return to != pos && self.move_cursor(to);
+    }
+
+    pub fn forward_word(&mut self) -> bool {
+        if self.line.part.is_empty() && !self.suggestion.is_empty() {
+            return self.accept_suggestion(false);
+        }
+        let text:Vec<char> = self.line.text().chars().collect();
+        let pos = self.cursor();
+        let to = word_forward(&text, pos);
+        return to != pos && self.move_cursor(to);
+    }
+
+    pub fn backward_kill_word(&mut self) -> bool {
+        let text:Vec<char> = self.line.text().chars().collect();
+        let pos = self.cursor();
+        return self.kill_range(word_back(&text, pos), pos, true);
+    }
+
+    pub fn kill_word(&mut self) -> bool {
+        let text:Vec<char> = self.line.text().chars().collect();
+        let pos = self.cursor();
+        return self.kill_range(pos, word_forward(&text, pos), false);
+    }
+
+    pub fn unix_line_discard(&mut self) -> bool {
+        let pos = self.cursor();
+        return self.kill_range(0, pos, true);
+    }
+
+    fn insert_yank(&mut self, text:Option<String>) -> bool {
+        let text = match text {
+            None => return false,
+            Some(t) => t
+        };
+        self.yanked = text.chars().count();
+        return self.insert_str(text.as_slice());
+    }
+
+    pub fn yank(&mut self) -> bool {
+        let text = self.kill_ring.yank();
+        return self.insert_yank(text);
     }
 
-    pub fn handle_escape(&mut self, ch:char) -> bool {
-        match ch {
-            ESC => {
-                self.escape = false;
+    // swap the text just yanked for the kill before it
+    pub fn yank_pop(&mut self) -> bool {
+        if self.last_action != "yank" && self.last_action != "yank-pop" {
+            return false;
+        }
+        for _ in 0..self.yanked {
+            if !self.delete_char() {
+                return false;
+            }
+        }
+        let text = self.kill_ring.rotate();
+        return self.insert_yank(text);
+    }
+
+    fn replace_line(&mut self, line:InputLine) {
+        self.clear_entire_line();
+        self.line = line;
+        self.controls.outs(self.line.fpart.as_slice());
+        self.bpart.clear();
+        self.idraw_part();
+    }
+
+    // write the prompt, with rprompt on its last line if there's room
+    pub fn draw_prompt(&mut self) {
+        match term_winsize() {
+            Err(_) => {/* keep the last size */},
+            Ok(size) => self.controls.update_size(size)
+        }
+        let prompt = self.prompt.clone();
+        self.controls.outs(prompt.as_slice());
+        let width = prompt_width(prompt.as_slice()) + visible_width(self.rprompt.as_slice());
+        if !self.rprompt.is_empty() && width < self.controls.width() {
+            let rprompt = self.rprompt.clone();
+            self.controls.outs_right(rprompt.as_slice());
+        }
+    }
+
+    // clearing to the end of the row takes rprompt with it, so it's drawn
+    // again after every change while the line still fits beside it
+    fn draw_rprompt(&mut self) {
+        if self.rprompt.is_empty() || self.search.is_some() || !self.pasted.is_empty() {
+            return;
+        }
+        let text = self.line.text();
+        if text.contains(NL) {
+            return;
+        }
+        let width = prompt_width(self.prompt.as_slice()) + str_width(text.as_slice()) +
+            str_width(self.suggestion.as_slice()) + visible_width(self.rprompt.as_slice());
+        if width < self.controls.width() {
+            let rprompt = self.rprompt.clone();
+            self.controls.outs_right(rprompt.as_slice());
+        }
+    }
+
+    // run the prompt again, after the mode changed
+    pub fn redraw_prompt(&mut self, prompt:String, rprompt:String) {
+        let len = prompt_width(self.prompt.as_slice()) + str_width(self.line.fpart.as_slice());
+        self.controls.cursors_left(len);
+        // back to the first line of the prompt, and clear everything after it
+        self.controls.rows_up(prompt_rows(self.prompt.as_slice()));
+        self.controls.clear_below();
+        self.prompt = prompt;
+        self.rprompt = rprompt;
+        self.draw_prompt();
+        self.controls.outs(self.line.fpart.as_slice());
+        self.bpart.clear();
+        self.idraw_part();
+    }
+
+    fn set_mode(&mut self, mode:EditMode) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.mode_changed = true;
+        }
+    }
+
+    fn enter_normal(&mut self) {
+        if self.mode == EditMode::Insert {
+            // like vi, the cursor goes back onto the last character typed
+            self.backward_char();
+            self.set_mode(EditMode::Normal);
+            self.vi_parser.reset();
+        }
+    }
+
+    // position of the cursor, in characters
+    fn cursor(&self) -> usize {
+        self.line.fpart.chars().count()
+    }
+
+    fn move_cursor(&mut self, to:usize) -> bool {
+        let mut at = self.cursor();
+        while at > to {
+            if !self.backward_char() {
+                return false;
+            }
+            at -= 1;
+        }
+        while at < to {
+            if !self.forward_char() {
+                return false;
+            }
+            at += 1;
+        }
+        return true;
+    }
+
+    fn delete_range(&mut self, from:usize, to:usize) -> bool {
+        if !self.move_cursor(to) {
+            return false;
+        }
+        for _ in from..to {
+            if !self.delete_char() {
+                return false;
+            }
+        }
+        return true;
+    }
+
+    pub fn undo(&mut self) -> bool {
+        let current = self.edit_state();
+        match self.undo.undo(current) {
+            None => return false,
+            Some(state) => self.restore(state)
+        }
+        self.undone = true;
+        return true;
+    }
+
+    pub fn redo(&mut self) -> bool {
+        let current = self.edit_state();
+        match self.undo.redo(current) {
+            None => return false,
+            Some(state) => self.restore(state)
+        }
+        self.undone = true;
+        return true;
+    }
+
+    fn edit_state(&self) -> EditState {
+        EditState {
+            pasted: self.pasted.clone(),
+            line: self.line.clone()
+        }
+    }
+
+    // keep the edit from before a change so it can be undone
+    fn record_change(&mut self, before:EditState) {
+        let typing = self.last_action == "self-insert";
+        // after c in normal mode, what's typed next is part of the change
+        let group_next = typing || (self.last_action.is_empty() && self.mode == EditMode::Insert);
+        self.undo.record(before, typing, group_next);
+    }
+
+    // put back an edit, going up to the first pasted line that differs
+    // and drawing it again from there
+    fn restore(&mut self, state:EditState) {
+        let same = self.pasted.iter().zip(state.pasted.iter())
+            .take_while(|&(a, b)| a.text() == b.text()).count();
+        while self.pasted.len() > same {
+            self.clear_entire_line();
+            // back over the newline
+            self.controls.cursors_left(1);
+            self.line = self.pasted.pop().unwrap();
+        }
+        self.clear_entire_line();
+        for line in state.pasted[same..].iter() {
+            self.controls.outs(line.text().as_slice());
+            self.controls.outc(NL);
+            self.pasted.push(line.clone());
+        }
+        self.replace_line(state.line);
+    }
+
+    fn handle_vi(&mut self, ch:char) -> bool {
+        self.last_action.clear();
+        let command = match self.vi_parser.push(ch) {
+            Pending => return true,
+            Invalid => return false,
+            Command(c) => c
+        };
+        let text:Vec<char> = self.line.text().chars().collect();
+        let pos = self.cursor();
+        match command {
+            Move(motion, count) => {
+                let to = motion_target(&text, pos, motion, count);
+                return to != pos && self.move_cursor(to);
             },
-            ANSI => {
-                if self.escape_chars.is_empty() {
-                    self.escape_chars.push(ANSI);
-                } else {
-                    self.escape = false;
+            Operate(op, motion, count) => {
+                let (from, to) = motion_range(&text, pos, motion, count);
+                if from == to && op != Change {
+                    return false;
                 }
-            },
-            'D' => {
-                // left
-                self.escape = false;
-                if self.line.left() {
-                    self.bpart.clear();
-                    self.controls.cursor_left();
-                } else {
+                self.register = text[from..to].iter().map(|c| *c).collect();
+                if op == Yank {
+                    return self.move_cursor(from);
+                }
+                if !self.delete_range(from, to) {
                     return false;
                 }
+                if op == Change {
+                    self.set_mode(EditMode::Insert);
+                }
+                return true;
             },
-            'C' => {
-                // right
-                self.escape = false;
-                if self.line.right() {
-                    self.bpart.clear();
-                    self.controls.cursor_right();
-                } else {
+            DeleteChar(count) => {
+                let to = min(pos + count, text.len());
+                if to == pos {
                     return false;
                 }
+                self.register = text[pos..to].iter().map(|c| *c).collect();
+                return self.delete_range(pos, to);
             },
-            'B' => {
-                // down
-                self.escape = false;
-                match self.bhistory.pop() {
-                    None if !self.line.is_empty() => {
-                        self.history.push_front(self.line.clone());
-                        self.clear_entire_line();
-                    },
-                    None => return false,
-                    Some(line) => {
-                        self.history.push_front(self.line.clone());
-                        self.clear_entire_line();
-                        self.line = line;
-                        self.controls.outs(self.line.fpart.as_slice());
-                        self.bpart.clear();
-                        self.idraw_part();
+            Put(count, after) => {
+                if self.register.is_empty() {
+                    return false;
+                }
+                if after && pos < text.len() && !self.forward_char() {
+                    return false;
+                }
+                let register = self.register.clone();
+                for _ in 0..count {
+                    if !self.insert_str(register.as_slice()) {
+                        return false;
                     }
                 }
+                return true;
             },
-            'A' => {
-                // up
-                self.escape = false;
-                match self.history.pop_front() {
-                    None => return false,
-                    Some(line) => {
-                        self.bhistory.push(self.line.clone());
-                        self.clear_entire_line();
-                        self.line = line;
-                        self.controls.outs(self.line.fpart.as_slice());
-                        self.bpart.clear();
-                        self.idraw_part();
+            Undo => return self.undo(),
+            HistoryPrev(count) => {
+                for _ in 0..count {
+                    if !self.history_prev() {
+                        return false;
                     }
                 }
+                return true;
             },
-            'R' => {
-                // cursor position
-                self.escape = false;
-                if !PPOS_REGEX.is_match(self.escape_chars.as_slice()) {
-                    return false;
-                } else {
-                    let caps = PPOS_REGEX.captures(self.escape_chars.as_slice()).unwrap();
-                    let row = caps.at(1).unwrap();
-                    let col = caps.at(2).unwrap();
-                    let pointer = Position {
-                        row: match from_str_radix(row, 10) {
-                            Err(_) => return false,
-                            Ok(v) => v
-                        },
-                        col: match from_str_radix(col, 10) {
-                            Err(_) => return false,
-                            Ok(v) => v
-                        }
-                    };
-                    self.controls.update_cursor(pointer);
-                    if !self.first_rsave {
-                        self.controls.save_row(pointer.col);
-                        self.controls.update_line(pointer.col);
-                        self.first_rsave = true;
+            HistoryNext(count) => {
+                for _ in 0..count {
+                    if !self.history_next() {
+                        return false;
                     }
                 }
+                return true;
             },
-            ch => {
-                self.escape_chars.push(ch);
-                if self.escape_chars.len() > MAX_ESCAPE {
-                    self.escape = false;
-                    return false;
-                }
+            StartInsert(at) => {
+                let to = match at {
+                    Cursor => pos,
+                    After => min(pos + 1, text.len()),
+                    Start => 0,
+                    End => text.len()
+                };
+                self.set_mode(EditMode::Insert);
+                return self.move_cursor(to);
             }
         }
+    }
+
+    fn clear_entire_line(&mut self) {
+        self.controls.cursors_left(str_width(self.line.fpart.as_slice()));
+        self.controls.clear_line_to(str_width(self.line.fpart.as_slice()) + str_width(self.line.part.as_slice()));
+        self.bpart.clear();
+        self.line.clear();
+    }
+
+    fn cursor_report(&mut self, row:usize, col:usize) -> bool {
+        let pointer = Position {
+            row: row,
+            col: col
+        };
+        self.controls.update_cursor(pointer);
+        if !self.first_rsave {
+            self.controls.save_row(pointer.col);
+            self.controls.update_line(pointer.col);
+            self.first_rsave = true;
+        }
         return true;
     }
 }
@@ -16,6 +16,7 @@ use std::fs;
 use std::old_io;
 
 use constants::*;
+use error::*;
 
 pub struct WashScript {
     pub path: PathBuf,
@@ -31,7 +32,7 @@ impl Drop for WashScript {
         match self.close() {
             Ok(_) => {},
             Err(e) => {
-                old_io::stdio::stderr().write_str(e.as_slice()).unwrap();
+                old_io::stdio::stderr().write_str(format!("{}", e).as_slice()).unwrap();
             }
         }
     }
@@ -61,25 +62,25 @@ impl WashScript {
         !self.handle.is_null()
     }
 
-    pub unsafe fn get_run(&self) -> Result<&c_void, String> {
+    pub unsafe fn get_run(&self) -> WashResult<&c_void> {
         match self.run_ptr.as_ref() {
             Some(f) => return Ok(f),
             None => {
-                return Err("Script cannot be run directly".to_string());
+                return Err(WashError::compile("Script cannot be run directly".to_string()));
             }
         }
     }
 
-    pub unsafe fn get_load(&self) -> Result<&c_void, String> {
+    pub unsafe fn get_load(&self) -> WashResult<&c_void> {
         match self.load_ptr.as_ref() {
             Some(f) => return Ok(f),
             None => {
-                return Err("Script has no load actions".to_string());
+                return Err(WashError::compile("Script has no load actions".to_string()));
             }
         }
     }
 
-    pub fn close(&mut self) -> Result<(), String> {
+    pub fn close(&mut self) -> WashResult<()> {
         if self.is_compiled() {
             // prevent memory leaks
             unsafe {
@@ -90,7 +91,7 @@ impl WashScript {
                     _ => {
                         let c = dlerror();
                         let e = String::from_utf8_lossy(ffi::CStr::from_ptr(c).to_bytes());
-                        return Err(format!("Couldn't unload wash script: {}\n", e));
+                        return Err(WashError::compile(format!("Couldn't unload wash script: {}\n", e)));
                     }
                 }
             }
@@ -101,18 +102,18 @@ impl WashScript {
         return Ok(());
     }
 
-    pub fn compile(&mut self) -> Result<bool, String> {
+    pub fn compile(&mut self, cache:&Path) -> WashResult<bool> {
         if self.is_compiled() {
             // script is already compiled
             return Ok(true);
         }
         if !self.path.exists() {
-            return Err(format!("Could not find {}", self.path.display()));
+            return Err(WashError::compile(format!("Could not find {}", self.path.display())));
         }
         let mut inf = match File::open(&self.path) {
             Ok(f) => f,
             Err(e) => {
-                return Err(format!("File error: {}", e));
+                return Err(WashError::compile(format!("File error: {}", e)));
             }
         };
         let mut content_s = String::new();
@@ -122,23 +123,23 @@ impl WashScript {
         let outp = {
             let mut outname = self.hash.clone();
             outname.push_str(".wo");
-            Path::new(WO_PATH).join(Path::new(outname.as_slice()))
+            cache.join(Path::new(outname.as_slice()))
         };
         if !outp.exists() {
             // scripts needs to be compiled
-            match fs::create_dir_all(Path::new(WO_PATH)) {
+            match fs::create_dir_all(cache) {
                 Ok(_) => {
                     // nothing
                 },
                 Err(e) => {
-                    return Err(format!("Couldn't create wash script cache directory: {}", e));
+                    return Err(WashError::compile(format!("Couldn't create wash script cache directory: {}", e)));
                 }
             }
             let mut command = Command::new("rustc");
             command.args(&["-o", outp.as_os_str().to_str().unwrap(), "-"]);
             let mut child = match command.spawn() {
                 Err(e) => {
-                    return Err(format!("Couldn't start compiler: {}", e));
+                    return Err(WashError::compile(format!("Couldn't start compiler: {}", e)));
                 },
                 Ok(c) => c
             };
@@ -152,12 +153,12 @@ impl WashScript {
 
             match child.wait_with_output() {
                 Err(e) => {
-                    return Err(format!("Compiler failed to run: {}", e));
+                    return Err(WashError::compile(format!("Compiler failed to run: {}", e)));
                 },
                 Ok(o) => {
                     if !o.status.success() {
-                        return Err(format!("Couldn't compile script: {}",
-                                           String::from_utf8_lossy(o.stderr.as_slice())));
+                        return Err(WashError::compile(format!("Couldn't compile script: {}",
+                                           String::from_utf8_lossy(o.stderr.as_slice()))));
                     }
                 }
             }
@@ -171,7 +172,7 @@ impl WashScript {
             if self.handle.is_null() {
                 let c = dlerror();
                 let e = String::from_utf8_lossy(ffi::CStr::from_ptr(c).to_bytes());
-                return Err(format!("Could not load script object: {}", e));
+                return Err(WashError::compile(format!("Could not load script object: {}", e)));
             }
             
             self.run_ptr = dlsym(self.handle, run_cstr.as_ptr());
@@ -190,7 +191,7 @@ impl WashScript {
         }
         if self.load_ptr.is_null() && self.run_ptr.is_null() {
             try!(self.close());
-            return Err("No load or run function found in script object".to_string());
+            return Err(WashError::compile("No load or run function found in script object".to_string()));
         }
         // success!
         return Ok(true);
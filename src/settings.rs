@@ -0,0 +1,259 @@
+// Runtime settings, available as the $cfg: variable path
+use std::collections::*;
+use std::num::*;
+
+use types::*;
+use types::WashArgs::*;
+use constants::*;
+use error::*;
+use highlight::*;
+
+use self::SettingValue::*;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum SettingValue {
+    Number(usize),
+    Text(String),
+    Flag(bool)
+}
+
+// checks a value before it's set, the type has already been checked
+pub type SettingCheck = fn(&SettingValue) -> WashResult<()>;
+
+struct SettingEntry {
+    value: SettingValue,
+    default: SettingValue,
+    check: SettingCheck
+}
+
+pub struct Settings {
+    entries: HashMap<String, SettingEntry>,
+    // names of settings changed since the last take_changed
+    changed: Vec<String>
+}
+
+impl SettingValue {
+    pub fn as_args(&self) -> WashArgs {
+        match self {
+            &Number(n) => Flat(format!("{}", n)),
+            &Text(ref s) => Flat(s.clone()),
+            // so flags work as conditions
+            &Flag(true) => Flat(format!("true")),
+            &Flag(false) => Empty
+        }
+    }
+
+    // read a new value of the same type as this one
+    pub fn parse(&self, val:&WashArgs) -> WashResult<SettingValue> {
+        if !val.is_flat() {
+            return Err(WashError::runtime(format!("Settings can only be flat")));
+        }
+        let s = val.flatten();
+        match self {
+            &Number(_) => match from_str_radix(s.as_slice(), 10) {
+                Err(_) => Err(WashError::runtime(format!("\"{}\" is not a number", s))),
+                Ok(n) => Ok(Number(n))
+            },
+            &Text(_) => Ok(Text(s)),
+            &Flag(_) => match s.as_slice() {
+                "true" | "on" | "yes" | "1" => Ok(Flag(true)),
+                "false" | "off" | "no" | "0" => Ok(Flag(false)),
+                _ => Err(WashError::runtime(format!("\"{}\" is not true or false", s)))
+            }
+        }
+    }
+}
+
+fn check_any(_:&SettingValue) -> WashResult<()> {
+    Ok(())
+}
+
+fn check_not_empty(val:&SettingValue) -> WashResult<()> {
+    match val {
+        &Text(ref s) if s.is_empty() => Err(WashError::runtime(format!("Setting cannot be empty"))),
+        _ => Ok(())
+    }
+}
+
+fn check_editing_mode(val:&SettingValue) -> WashResult<()> {
+    match val {
+        &Text(ref s) if *s != "emacs" && *s != "vi" =>
+            Err(WashError::runtime(format!("Editing mode must be emacs or vi"))),
+        _ => Ok(())
+    }
+}
+
+fn check_colors(val:&SettingValue) -> WashResult<()> {
+    match val {
+        &Text(ref s) => parse_colors(s.as_slice()).map(|_| ()),
+        _ => Ok(())
+    }
+}
+
+fn check_escape(val:&SettingValue) -> WashResult<()> {
+    match val {
+        // the longest sequence we handle is a cursor position report
+        &Number(n) if n < 8 => Err(WashError::runtime(format!("Escape length must be at least 8"))),
+        _ => Ok(())
+    }
+}
+
+fn check_strf(val:&SettingValue) -> WashResult<()> {
+    match val {
+        &Number(n) if n == 0 || n > 65536 =>
+            Err(WashError::runtime(format!("Time buffer size must be between 1 and 65536"))),
+        _ => Ok(())
+    }
+}
+
+impl Settings {
+    pub fn new() -> Settings {
+        let mut settings = Settings {
+            entries: HashMap::new(),
+            changed: vec![]
+        };
+        settings.add("history_size", Number(HISTORY_SIZE), check_any);
+        // empty to not keep history between sessions
+        settings.add("history_file", Text(HISTORY_PATH.to_string()), check_any);
+        settings.add("wo_path", Text(WO_PATH.to_string()), check_not_empty);
+        settings.add("max_escape", Number(MAX_ESCAPE), check_escape);
+        settings.add("escape_timeout", Number(ESCAPE_TIMEOUT), check_any);
+        settings.add("strf_buf_size", Number(STRF_BUF_SIZE), check_strf);
+        // failed commands stop the current block or function
+        settings.add("strict", Flag(false), check_any);
+        settings.add("editing_mode", Text("emacs".to_string()), check_editing_mode);
+        settings.add("highlight", Flag(true), check_any);
+        // show the rest of a line from history while typing
+        settings.add("autosuggest", Flag(true), check_any);
+        settings.add("colors", Text(DEFAULT_COLORS.to_string()), check_colors);
+        // empty to not use bash completion
+        settings.add("bash_completion", Text(BASH_COMPLETION_PATH.to_string()), check_any);
+        return settings;
+    }
+
+    pub fn add(&mut self, name:&str, default:SettingValue, check:SettingCheck) {
+        self.entries.insert(name.to_string(), SettingEntry {
+            value: default.clone(),
+            default: default,
+            check: check
+        });
+    }
+
+    pub fn has(&self, name:&String) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn get(&self, name:&str) -> Option<&SettingValue> {
+        match self.entries.get(name) {
+            None => None,
+            Some(entry) => Some(&entry.value)
+        }
+    }
+
+    // typed getters for subsystems, which know the types of their settings
+    pub fn number(&self, name:&str) -> usize {
+        match self.get(name) {
+            Some(&Number(n)) => n,
+            _ => panic!("Setting {} is not a number", name)
+        }
+    }
+
+    pub fn text(&self, name:&str) -> String {
+        match self.get(name) {
+            Some(&Text(ref s)) => s.clone(),
+            _ => panic!("Setting {} is not text", name)
+        }
+    }
+
+    pub fn flag(&self, name:&str) -> bool {
+        match self.get(name) {
+            Some(&Flag(b)) => b,
+            _ => panic!("Setting {} is not a flag", name)
+        }
+    }
+
+    pub fn set(&mut self, name:&String, val:&WashArgs) -> WashResult<WashArgs> {
+        let value = match self.entries.get(name) {
+            None => return Err(WashError::runtime(format!("Unknown setting: {}", name))),
+            Some(entry) => {
+                let value = try!(entry.value.parse(val));
+                match (entry.check)(&value) {
+                    Err(e) => return Err(WashError::runtime(format!("Invalid value for {}", name))
+                                         .caused_by(e)),
+                    Ok(_) => {}
+                }
+                value
+            }
+        };
+        let out = value.as_args();
+        self.update(name, value);
+        return Ok(out);
+    }
+
+    pub fn reset(&mut self, name:&String) -> WashResult<WashArgs> {
+        let value = match self.entries.get(name) {
+            None => return Err(WashError::runtime(format!("Unknown setting: {}", name))),
+            Some(entry) => entry.default.clone()
+        };
+        self.update(name, value);
+        return Ok(Empty);
+    }
+
+    fn update(&mut self, name:&String, value:SettingValue) {
+        let entry = self.entries.get_mut(name).unwrap();
+        if entry.value != value {
+            entry.value = value;
+            if !self.changed.contains(name) {
+                self.changed.push(name.clone());
+            }
+        }
+    }
+
+    pub fn get_args(&self, name:&String) -> WashResult<WashArgs> {
+        match self.entries.get(name) {
+            None => Err(WashError::runtime(format!("Unknown setting: {}", name))),
+            Some(entry) => Ok(entry.value.as_args())
+        }
+    }
+
+    pub fn all_args(&self) -> WashArgs {
+        let mut names:Vec<&String> = self.entries.keys().collect();
+        names.sort();
+        let mut out = vec![];
+        for name in names.iter() {
+            out.push(Long(vec![Flat((*name).clone()),
+                               self.entries.get(*name).unwrap().value.as_args()]));
+        }
+        return Long(out);
+    }
+
+    // settings changed since this was last called, so the subsystems
+    // using them can be told
+    pub fn take_changed(&mut self) -> Vec<String> {
+        let mut out = vec![];
+        out.append(&mut self.changed);
+        return out;
+    }
+}
+
+#[test]
+fn settings_set_test() {
+    let mut settings = Settings::new();
+    let name = "history_size".to_string();
+    assert!(settings.set(&name, &Flat("20".to_string())).is_ok());
+    assert_eq!(settings.number("history_size"), 20);
+    assert_eq!(settings.take_changed(), vec![name.clone()]);
+    assert!(settings.take_changed().is_empty());
+    assert!(settings.set(&name, &Flat("lots".to_string())).is_err());
+    assert!(settings.reset(&name).is_ok());
+    assert_eq!(settings.number("history_size"), HISTORY_SIZE);
+}
+
+#[test]
+fn settings_check_test() {
+    let mut settings = Settings::new();
+    assert!(settings.set(&"max_escape".to_string(), &Flat("2".to_string())).is_err());
+    assert_eq!(settings.number("max_escape"), MAX_ESCAPE);
+    assert!(settings.set(&"wo_path".to_string(), &Flat("".to_string())).is_err());
+    assert!(settings.set(&"no_such_setting".to_string(), &Flat("1".to_string())).is_err());
+}
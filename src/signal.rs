@@ -184,6 +184,8 @@ extern {
     fn pselect(nfds:c_int, readfds:*mut FdSet, writefds:*mut FdSet,
                exceptfds:*mut FdSet, timeout:*const timespec, sigmask:*const SigSet) -> c_int;
     fn sigprocmask(how:c_int, set:*const SigSet, old_set:*mut SigSet) -> c_int;
+    fn syscall(number:c_long, ...) -> c_long;
+    fn getpid() -> pid_t;
 }
 
 pub fn signal_proc_mask(how:c_int, set:&SigSet) -> io::Result<SigSet> {
@@ -250,7 +252,7 @@ pub fn select(read:&Vec<Fd>, write:&Vec<Fd>, except:&Vec<Fd>,
         Some(t) => {
             let time = timespec {
                 tv_sec: (t / 1000) as c_longlong,
-                tv_nsec: ((t % 1000) * 1000) as c_long
+                tv_nsec: ((t % 1000) * 1000000) as c_long
             };
             unsafe {pselect(max + 1, &mut readfds, &mut writefds,
                             &mut exceptfds, &time, sigmask)}
@@ -299,7 +301,7 @@ pub fn signal_wait_set(set:&SigSet, timeout:Option<usize>) -> io::Result<SigInfo
         Some(t) => {
             let time = timespec {
                 tv_sec: (t / 1000) as c_longlong,
-                tv_nsec: ((t % 1000) * 1000) as c_long
+                tv_nsec: ((t % 1000) * 1000000) as c_long
             };
             match unsafe {sigtimedwait(set, &mut info, &time)} {
                 v if v > 0 => Ok(info),
@@ -309,6 +311,22 @@ pub fn signal_wait_set(set:&SigSet, timeout:Option<usize>) -> io::Result<SigInfo
     }
 }
 
+// whether signal_requeue works here
+pub fn can_requeue() -> bool {
+    SYS_RT_SIGQUEUEINFO.is_some()
+}
+
+// send a signal that was already taken back to ourselves, fields and all,
+// so whatever handles it still gets it. Check can_requeue first
+pub fn signal_requeue(info:&SigInfo) -> io::Result<()> {
+    let number = SYS_RT_SIGQUEUEINFO.expect("rt_sigqueueinfo isn't known here");
+    match unsafe {syscall(number, getpid() as c_long,
+                          info.signo as c_long, info as *const SigInfo)} {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(())
+    }
+}
+
 pub fn signal_handle(signal:c_int, action:*const SigAction) -> io::Result<SigAction> {
     unsafe {
         let old_act = &mut SigAction::new();
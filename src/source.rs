@@ -0,0 +1,297 @@
+use std::fs::{File, PathExt};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::old_io::process::ProcessExit::*;
+
+use input::*;
+use types::*;
+use ast::*;
+use env::*;
+use constants::*;
+use util::*;
+use error::*;
+
+// Feeds wash source that doesn't come from the line editor (script files
+// and the like) through an InputLine one character at a time, the same
+// way the reader does. This means literals can span lines, and blocks
+// like func! and while! are collected by the AST before being run.
+pub struct SourceReader {
+    pub name: String,
+    pub line: InputLine,
+    pub lineno: usize,
+    pub result: WashResult<WashArgs>,
+    // line the current command started on
+    start: usize,
+    // set when a line had a syntax error, the rest of it is skipped
+    skip: bool
+}
+
+impl SourceReader {
+    pub fn new(name:&str) -> SourceReader {
+        SourceReader {
+            name: name.to_string(),
+            line: {
+                let mut line = InputLine::new();
+                line.pos = SourcePos::new(name, 1);
+                line
+            },
+            lineno: 1,
+            result: Ok(WashArgs::Empty),
+            start: 1,
+            skip: false
+        }
+    }
+
+    pub fn feed_str(&mut self, s:&str, ast:&mut AST, env:&mut WashEnv) {
+        for ch in s.chars() {
+            self.feed(ch, ast, env);
+        }
+    }
+
+    pub fn feed(&mut self, u_ch:char, ast:&mut AST, env:&mut WashEnv) {
+        if env.exit.is_some() {
+            // exit was called, ignore the rest
+            return;
+        }
+        // tabs are just whitespace in files
+        let ch = if u_ch == '\t' {SPC} else {u_ch};
+        if self.skip {
+            if ch == NL {
+                self.skip = false;
+                self.lineno += 1;
+                self.next_command();
+            }
+            return;
+        }
+        if ch == NL {
+            self.lineno += 1;
+            if !self.line.push(NL) {
+                // newlines outside of literals end the line
+                self.run_line(ast, env);
+            }
+        } else if self.line.is_empty() && ch == SPC {
+            // skip indentation
+        } else if !self.line.push(ch) {
+            let start = self.start;
+            self.report(start, WashError::parse(format!("Unexpected character \"{}\"", ch)), env);
+            self.line.clear();
+            ast.clear();
+            self.skip = true;
+        }
+    }
+
+    pub fn finish(&mut self, ast:&mut AST, env:&mut WashEnv) {
+        if !self.skip && !self.line.is_empty() {
+            self.run_line(ast, env);
+        }
+        if ast.in_block() {
+            let lineno = self.lineno;
+            self.report(lineno, WashError::parse(format!("Unexpected end of input inside a block")), env);
+            ast.clear();
+        }
+        self.line.clear();
+        self.skip = false;
+    }
+
+    fn next_command(&mut self) {
+        self.start = self.lineno;
+        self.line.pos.line = self.lineno;
+    }
+
+    fn report(&mut self, line:usize, e:WashError, env:&mut WashEnv) {
+        if e.kind == ErrorKind::Parse {
+            // the line never ran, so nothing else set a status
+            env.set_exit(ExitStatus(2));
+        }
+        match e.location {
+            // errors from running code know where they came from
+            Some(ref pos) => env.errf(format_args!("{}: Error: {}\n", pos, e)),
+            None => env.errf(format_args!("{}:{}: Error: {}\n", self.name, line, e))
+        }
+        for frame in e.backtrace().iter() {
+            env.errf(format_args!("    {}\n", frame));
+        }
+        self.result = Err(e);
+    }
+
+    fn run_line(&mut self, ast:&mut AST, env:&mut WashEnv) {
+        let start = self.start;
+        let pos = self.line.pos.clone();
+        self.next_command();
+        if self.line.is_empty() {
+            return;
+        }
+        let mut value = match self.line.process() {
+            None => {
+                self.report(start, WashError::parse(format!("Unbalanced parentheses")), env);
+                self.line.clear();
+                ast.clear();
+                return;
+            },
+            Some(v) => v
+        };
+        self.line.clear();
+        match run_line(&mut value, pos, ast, env) {
+            None => {/* block isn't finished yet */},
+            Some(Err(ref e)) if e.is_stop() => {
+                // the silent error
+                self.result = Err(e.clone());
+            },
+            Some(Err(e)) => self.report(start, e, env),
+            Some(Ok(WashArgs::Empty)) => {
+                self.result = Ok(WashArgs::Empty);
+            },
+            Some(Ok(v)) => {
+                println!("{}", v.flatten());
+                self.result = Ok(v);
+            }
+        }
+    }
+}
+
+// Adds a line to the AST, and runs everything collected so far if that
+// line finished the current block.
+// Returns None if the AST is still waiting on more lines.
+pub fn run_line(line:&mut InputValue, pos:SourcePos, ast:&mut AST,
+                env:&mut WashEnv) -> Option<WashResult<WashArgs>> {
+    match ast.add_line(line, pos) {
+        Err(e) => {
+            ast.clear();
+            env.set_exit(ExitStatus(2));
+            return Some(Err(e));
+        },
+        Ok(_) if ast.in_block() => return None,
+        Ok(_) => {}
+    }
+    match ast.optimize() {
+        Err(e) => {
+            println!("Optimization error: {}", e);
+        },
+        Ok(_) => {}
+    }
+    env.exit_set = false;
+    let result = ast.into_runner().evaluate(&WashArgs::Empty, env);
+    match result {
+        Err(ref e) if !e.is_stop() => {
+            if !env.exit_set || env.exit_status() == 0 {
+                // failed without a command failing
+                env.set_exit(ExitStatus(1));
+            }
+            env.last_error = Some(e.clone());
+        },
+        Ok(_) if !env.exit_set => {
+            // nothing that sets a status ran, like an assignment
+            env.set_exit(ExitStatus(0));
+        },
+        _ => {}
+    }
+    return Some(result);
+}
+
+// a line from the line editor. Blocks opened by the lines before it stay
+// open, so the lines of a paste are run through here one at a time too
+pub fn run_input(line:&InputLine, ast:&mut AST, env:&mut WashEnv) -> Option<WashResult<WashArgs>> {
+    let mut value = match line.process() {
+        None => {
+            ast.clear();
+            env.set_exit(ExitStatus(2));
+            return Some(Err(WashError::parse(format!("Unbalanced parentheses"))));
+        },
+        Some(v) => v
+    };
+    return run_line(&mut value, line.pos.clone(), ast, env);
+}
+
+// errors go to stderr, the same as errors in scripts
+pub fn print_result(result:&WashResult<WashArgs>, env:&mut WashEnv) {
+    match result {
+        &Err(ref e) if e.is_stop() => {
+            // the silent error
+        },
+        &Err(ref e) => {
+            env.errf(format_args!("Error: {}\n", e));
+            for frame in e.backtrace().iter() {
+                env.errf(format_args!("    {}\n", frame));
+            }
+        },
+        &Ok(WashArgs::Empty) => {
+            // print nothing
+        },
+        &Ok(ref v) => {
+            println!("{}", v.flatten());
+        }
+    }
+}
+
+// Runs a file of wash source, and returns an exit status for it
+pub fn run_file(path:&Path, ast:&mut AST, env:&mut WashEnv) -> i32 {
+    let mut contents = String::new();
+    match File::open(path) {
+        Err(e) => {
+            env.errf(format_args!("wash: {}: {}\n", path.display(), e));
+            return 127;
+        },
+        Ok(mut f) => match f.read_to_string(&mut contents) {
+            Err(e) => {
+                env.errf(format_args!("wash: {}: {}\n", path.display(), e));
+                return 126;
+            },
+            Ok(_) => {}
+        }
+    }
+    if contents.starts_with("#!") {
+        // skip the interpreter line, but keep the line numbers right
+        contents = match contents.find(NL) {
+            None => String::new(),
+            Some(i) => contents[i..].to_string()
+        };
+    }
+    let name = format!("{}", path.display());
+    return run_string(name.as_slice(), contents.as_slice(), ast, env);
+}
+
+// Runs the given wash source, and returns an exit status for it
+pub fn run_string(name:&str, contents:&str, ast:&mut AST, env:&mut WashEnv) -> i32 {
+    let mut source = SourceReader::new(name);
+    source.feed_str(contents, ast, env);
+    source.finish(ast, env);
+    env.flush();
+    return env.final_status();
+}
+
+// Runs the system-wide and user startup files, if they exist.
+// Errors in them are reported but don't stop the shell from starting.
+pub fn load_rc(ast:&mut AST, env:&mut WashEnv) {
+    for name in [SYSTEM_RC_PATH, USER_RC_PATH].iter() {
+        let path = expand_path(PathBuf::new(name));
+        if path.exists() {
+            run_file(path.as_path(), ast, env);
+        }
+    }
+}
+
+#[cfg(test)]
+fn input_line(text:&str) -> InputLine {
+    let mut line = InputLine::new();
+    for ch in text.chars() {
+        assert!(line.push(ch));
+    }
+    return line;
+}
+
+#[test]
+fn run_input_block_test() {
+    let mut env = WashEnv::new();
+    let mut ast = AST::new();
+    ::handlers::load_handlers(&mut ast);
+    // a block typed in by hand, with the rest of it pasted
+    assert!(run_input(&input_line("func! greet {"), &mut ast, &mut env).is_none());
+    assert!(ast.in_block());
+    for text in ["echo hi", "echo there"].iter() {
+        assert!(run_input(&input_line(text), &mut ast, &mut env).is_none());
+        assert!(ast.in_block());
+    }
+    assert!(run_input(&input_line("}"), &mut ast, &mut env).is_some());
+    assert!(!ast.in_block());
+    assert!(env.hasf(&"greet".to_string()));
+}
@@ -0,0 +1,21 @@
+// Constants termios.rs needs, apart from constants.rs so key_helper can
+// include them without bringing in regex
+#![allow(dead_code)]
+
+use libc::*;
+
+// standard file descriptiors
+pub const STDIN:c_int  = 0;
+pub const STDOUT:c_int = 1;
+pub const STDERR:c_int = 2;
+
+// select termios constants that we use
+pub const ISIG:c_uint     = 1;
+pub const ICANON:c_uint   = 2;
+pub const ECHO:c_uint     = 8;
+pub const IXON:c_uint     = 0o2000;
+pub const TCSANOW:c_int   = 0;
+pub const TCSADRAIN:c_int = 1;
+pub const TCSAFLUSH:c_int = 2;
+
+pub const NCCS:usize = 32;
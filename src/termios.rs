@@ -2,7 +2,7 @@ use libc::{c_uint, c_uchar, c_int};
 
 use std::io;
 
-use constants::*;
+use termconst::*;
 
 type CCType = c_uchar;
 type SpeedType = c_uint;
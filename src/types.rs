@@ -5,10 +5,12 @@ use std::cmp::*;
 
 use std::fmt;
 
+use error::*;
+
 use self::WashArgs::*;
 use self::Action::*;
 
-pub type AstResult = Result<HandlerResult, String>;
+pub type AstResult = WashResult<HandlerResult>;
 pub type SectionTable = HashMap<SectionType, LinkedList<Action>>;
 
 #[derive(Clone, Eq, Hash)]
@@ -33,6 +35,13 @@ pub struct Position {
     pub col: usize
 }
 
+// file (or other source) name and line an action came from
+#[derive(Clone, PartialEq)]
+pub struct SourcePos {
+    pub name: String,
+    pub line: usize
+}
+
 #[derive(Clone)]
 pub struct WashBlock {
     pub start: String,
@@ -72,6 +81,8 @@ pub enum Action {
     Call(String),
     // fail with the given message
     Fail(String),
+    // stop evaluation without a message
+    Halt,
     // take name, path stored on the top of VS (like $path:name)
     // store CFV in that name, path
     Store,
@@ -118,7 +129,9 @@ pub enum Action {
     Args,
     // index on the first element in VS
     // index is stored on the CFV
-    Index
+    Index,
+    // the following actions came from the given line
+    Mark(SourcePos)
 }
 
 impl PartialEq for HandlerResult {
@@ -161,6 +174,21 @@ impl Position {
     }
 }
 
+impl SourcePos {
+    pub fn new(name:&str, line:usize) -> SourcePos {
+        SourcePos {
+            name: name.to_string(),
+            line: line
+        }
+    }
+}
+
+impl fmt::Display for SourcePos {
+    fn fmt(&self, fmt:&mut fmt::Formatter) -> fmt::Result {
+        fmt.write_fmt(format_args!("{}:{}", self.name, self.line))
+    }
+}
+
 impl PartialEq for Action {
     fn eq(&self, other:&Action) -> bool {
         match self {
@@ -176,6 +204,10 @@ impl PartialEq for Action {
                 &Fail(ref om) if *m == *om => true,
                 _ => false
             },
+            &Halt => match other {
+                &Halt => true,
+                _ => false
+            },
             &Store => match other {
                 &Store => true,
                 _ => false
@@ -255,6 +287,10 @@ impl PartialEq for Action {
             &Index => match other {
                 &Index => true,
                 _ => false
+            },
+            &Mark(ref p) => match other {
+                &Mark(ref op) if *p == *op => true,
+                _ => false
             }
         }
     }
@@ -272,6 +308,9 @@ impl fmt::Debug for Action {
             &Fail(ref m) => {
                 try!(fmt.write_fmt(format_args!("Fail({})", m)));
             },
+            &Halt => {
+                try!(fmt.write_str("Halt"));
+            },
             &Store => {
                 try!(fmt.write_str("Store"));
             },
@@ -328,6 +367,9 @@ impl fmt::Debug for Action {
             },
             &Index => {
                 try!(fmt.write_fmt(format_args!("Index")));
+            },
+            &Mark(ref p) => {
+                try!(fmt.write_fmt(format_args!("Mark({})", p)));
             }
         }
         Ok(())
@@ -0,0 +1,88 @@
+// Undo and redo for the line editor: the state from before each change,
+// and from before each undo so it can be redone
+pub struct UndoLog<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    // set while what's typed goes into the last change
+    grouping: bool
+}
+
+impl<T> UndoLog<T> {
+    pub fn new() -> UndoLog<T> {
+        UndoLog {
+            undo: vec![],
+            redo: vec![],
+            grouping: false
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+        self.grouping = false;
+    }
+
+    // keep the state from before a change. Typing goes into the change
+    // before it when that one was left grouping, and group_next leaves
+    // this one open for what's typed next
+    pub fn record(&mut self, before:T, typing:bool, group_next:bool) {
+        if !(typing && self.grouping) {
+            self.undo.push(before);
+        }
+        self.redo.clear();
+        self.grouping = group_next;
+    }
+
+    // anything typed after this is a change of its own
+    pub fn end_group(&mut self) {
+        self.grouping = false;
+    }
+
+    // the state to go back to, with current kept to redo
+    pub fn undo(&mut self, current:T) -> Option<T> {
+        self.grouping = false;
+        match self.undo.pop() {
+            None => None,
+            Some(state) => {
+                self.redo.push(current);
+                Some(state)
+            }
+        }
+    }
+
+    // the state an undo left, with current kept to undo again
+    pub fn redo(&mut self, current:T) -> Option<T> {
+        self.grouping = false;
+        match self.redo.pop() {
+            None => None,
+            Some(state) => {
+                self.undo.push(current);
+                Some(state)
+            }
+        }
+    }
+}
+
+#[test]
+fn undo_log_test() {
+    let mut log = UndoLog::new();
+    assert_eq!(log.undo("".to_string()), None);
+    // typing "ls" a character at a time is one change
+    log.record("".to_string(), true, true);
+    log.record("l".to_string(), true, true);
+    // a kill is another
+    log.record("ls".to_string(), false, false);
+    assert_eq!(log.undo("".to_string()), Some("ls".to_string()));
+    assert_eq!(log.undo("ls".to_string()), Some("".to_string()));
+    assert_eq!(log.undo("".to_string()), None);
+    // and both come back
+    assert_eq!(log.redo("".to_string()), Some("ls".to_string()));
+    assert_eq!(log.redo("ls".to_string()), Some("".to_string()));
+    assert_eq!(log.redo("".to_string()), None);
+    assert_eq!(log.undo("".to_string()), Some("ls".to_string()));
+    // typing after an undo starts a new change, and can't be redone past
+    log.record("ls".to_string(), true, true);
+    assert_eq!(log.redo("ls -".to_string()), None);
+    assert_eq!(log.undo("ls -".to_string()), Some("ls".to_string()));
+    assert_eq!(log.undo("ls".to_string()), Some("".to_string()));
+}
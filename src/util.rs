@@ -2,6 +2,8 @@ use std::path::{Path, PathBuf, AsPath};
 
 use std::env;
 
+use constants::*;
+
 #[macro_export]
 macro_rules! tryp {
     ($e:expr) => ({
@@ -17,7 +19,7 @@ macro_rules! tryf {
     ($e:expr, $($arg:tt)*) => ({
         match $e {
             Ok(e) => e,
-            Err(e) => return Err(format!($($arg)*, err=e))
+            Err(e) => return Err(::std::error::FromError::from_error(format!($($arg)*, err=e)))
         }
     })
 }
@@ -35,6 +37,78 @@ pub fn build_string(ch:char, count:usize) -> String {
     }
 }
 
+// characters that take no columns: combining marks, zero width
+// spaces and joiners, and variation selectors
+const ZERO_WIDTH:[(u32, u32); 24] = [
+    (0x0300, 0x036F), (0x0483, 0x0489), (0x0591, 0x05BD), (0x05BF, 0x05BF),
+    (0x05C1, 0x05C2), (0x05C4, 0x05C5), (0x05C7, 0x05C7), (0x0610, 0x061A),
+    (0x064B, 0x065F), (0x0670, 0x0670), (0x06D6, 0x06DC), (0x06DF, 0x06E4),
+    (0x0900, 0x0902), (0x093C, 0x093C), (0x0941, 0x0948), (0x094D, 0x094D),
+    (0x0E31, 0x0E31), (0x0E34, 0x0E3A), (0x1AB0, 0x1AFF), (0x1DC0, 0x1DFF),
+    (0x200B, 0x200F), (0x20D0, 0x20FF), (0xFE00, 0xFE0F), (0xFE20, 0xFE2F)
+];
+
+// characters that take two columns: east asian wide and full width
+// characters, and emoji
+const DOUBLE_WIDTH:[(u32, u32); 16] = [
+    (0x1100, 0x115F), (0x2E80, 0x303E), (0x3041, 0x33FF), (0x3400, 0x4DBF),
+    (0x4E00, 0x9FFF), (0xA000, 0xA4CF), (0xAC00, 0xD7A3), (0xF900, 0xFAFF),
+    (0xFE30, 0xFE4F), (0xFF00, 0xFF60), (0xFFE0, 0xFFE6), (0x1F300, 0x1F64F),
+    (0x1F680, 0x1F6FF), (0x1F900, 0x1F9FF), (0x20000, 0x2FFFD), (0x30000, 0x3FFFD)
+];
+
+// columns ch takes up in a terminal
+pub fn char_width(ch:char) -> usize {
+    let c = ch as u32;
+    if c < 0x20 || (c >= 0x7F && c < 0xA0) {
+        // control characters don't print
+        return 0;
+    } else if c < 0x300 {
+        return 1;
+    } else if ZERO_WIDTH.iter().any(|&(lo, hi)| c >= lo && c <= hi) {
+        return 0;
+    } else if DOUBLE_WIDTH.iter().any(|&(lo, hi)| c >= lo && c <= hi) {
+        return 2;
+    }
+    return 1;
+}
+
+pub fn str_width(s:&str) -> usize {
+    s.chars().map(char_width).fold(0, |a, b| a + b)
+}
+
+// like str_width, but escape sequences like colors take no columns
+pub fn visible_width(s:&str) -> usize {
+    let mut width = 0;
+    let mut chars = s.chars();
+    loop {
+        match chars.next() {
+            None => return width,
+            Some(ESC) => match chars.next() {
+                // a control sequence ends with a character from @ to ~
+                Some(ANSI) => for ch in chars.by_ref() {
+                    if ch >= '@' && ch <= '~' {
+                        break;
+                    }
+                },
+                // an operating system command, like setting the title,
+                // ends with BEL or ESC \, a \ on its own is part of it
+                Some(']') => {
+                    let mut escaped = false;
+                    for ch in chars.by_ref() {
+                        if ch == BEL || (escaped && ch == '\\') {
+                            break;
+                        }
+                        escaped = ch == ESC;
+                    }
+                },
+                _ => {/* ESC and one more character */}
+            },
+            Some(ch) => width += char_width(ch)
+        }
+    }
+}
+
 pub fn expand_path(path:PathBuf) -> PathBuf {
     match path.clone().relative_from(Path::new("~")) {
         None => path,
@@ -77,3 +151,32 @@ fn condense_path_test() {
     assert!(condense_path(Path::new("/home/")) == Path::new("/home/"));
     assert!(condense_path(Path::new("/etc/wash/")) == Path::new("/etc/wash/"));
 }
+
+#[test]
+fn char_width_test() {
+    assert_eq!(char_width('a'), 1);
+    assert_eq!(char_width('\t'), 0);
+    assert_eq!(char_width('\u{301}'), 0);
+    assert_eq!(char_width('\u{200d}'), 0);
+    assert_eq!(char_width('漢'), 2);
+    assert_eq!(char_width('ｈ'), 2);
+    assert_eq!(char_width('\u{1f600}'), 2);
+    assert_eq!(char_width('é'), 1);
+}
+
+#[test]
+fn str_width_test() {
+    assert_eq!(str_width("wash"), 4);
+    assert_eq!(str_width("e\u{301}cole"), 5);
+    assert_eq!(str_width("日本語 ok"), 9);
+    assert_eq!(str_width(""), 0);
+}
+
+#[test]
+fn visible_width_test() {
+    assert_eq!(visible_width("\u{1b}[1;32mwash\u{1b}[0m"), 4);
+    assert_eq!(visible_width("\u{1b}]0;title\u{7}$ "), 2);
+    assert_eq!(visible_width("\u{1b}]0;C:\\wash\u{1b}\\$ "), 2);
+    assert_eq!(visible_width("\u{1b}[32m日本\u{1b}[0m"), 4);
+    assert_eq!(visible_width("\u{1b}7a\u{1b}8"), 1);
+}
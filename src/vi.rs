@@ -0,0 +1,293 @@
+// Vi editing mode: insert mode types like the normal bindings do, ESC
+// goes to normal mode, where keys are counts, motions and operators.
+// This reads normal mode keys into commands, LineReader carries them out
+use std::cmp::*;
+
+use self::EditMode::*;
+use self::ViMotion::*;
+use self::ViOperator::*;
+use self::ViCommand::*;
+use self::ViInput::*;
+use self::InsertAt::*;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EditMode {
+    Emacs,
+    Insert,
+    Normal
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ViMotion {
+    Left,
+    Right,
+    WordStart,
+    WordBack,
+    WordEnd,
+    LineStart,
+    LineEnd,
+    // dd, cc and yy
+    WholeLine
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ViOperator {
+    Delete,
+    Change,
+    Yank
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InsertAt {
+    Cursor,
+    After,
+    Start,
+    End
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ViCommand {
+    Move(ViMotion, usize),
+    Operate(ViOperator, ViMotion, usize),
+    DeleteChar(usize),
+    // put the register, after the cursor or before it
+    Put(usize, bool),
+    Undo,
+    HistoryPrev(usize),
+    HistoryNext(usize),
+    StartInsert(InsertAt)
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ViInput {
+    Pending,
+    Command(ViCommand),
+    Invalid
+}
+
+pub struct ViParser {
+    count: Option<usize>,
+    // operator waiting for a motion, and the count given before it
+    operator: Option<(char, usize)>
+}
+
+impl EditMode {
+    // for $sys:mode
+    pub fn name(&self) -> &'static str {
+        match self {
+            &Emacs => "emacs",
+            &Insert => "insert",
+            &Normal => "normal"
+        }
+    }
+}
+
+impl ViParser {
+    pub fn new() -> ViParser {
+        ViParser {
+            count: None,
+            operator: None
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.count = None;
+        self.operator = None;
+    }
+
+    pub fn push(&mut self, ch:char) -> ViInput {
+        // 0 is a motion unless it's part of a count
+        match ch.to_digit(10) {
+            Some(d) if d > 0 || self.count.is_some() => {
+                self.count = Some(self.count.unwrap_or(0) * 10 + d as usize);
+                return Pending;
+            },
+            _ => {}
+        }
+        let count = self.count.take().unwrap_or(1);
+        let motion = match ch {
+            'h' => Some(Left),
+            'l' | ' ' => Some(Right),
+            'w' => Some(WordStart),
+            'b' => Some(WordBack),
+            'e' => Some(WordEnd),
+            '0' | '^' => Some(LineStart),
+            '$' => Some(LineEnd),
+            _ => None
+        };
+        match self.operator.take() {
+            None => {},
+            Some((op, before)) => {
+                let motion = if ch == op {
+                    WholeLine
+                } else {
+                    match motion {
+                        None => return Invalid,
+                        // cw changes to the end of the word, like vi does
+                        Some(WordStart) if op == 'c' => WordEnd,
+                        Some(m) => m
+                    }
+                };
+                let operator = match op {
+                    'd' => Delete,
+                    'c' => Change,
+                    _ => Yank
+                };
+                return Command(Operate(operator, motion, before * count));
+            }
+        }
+        match motion {
+            None => {},
+            Some(m) => return Command(Move(m, count))
+        }
+        Command(match ch {
+            'd' | 'c' | 'y' => {
+                self.operator = Some((ch, count));
+                return Pending;
+            },
+            'D' => Operate(Delete, LineEnd, 1),
+            'C' => Operate(Change, LineEnd, 1),
+            'x' => DeleteChar(count),
+            'p' => Put(count, true),
+            'P' => Put(count, false),
+            'u' => Undo,
+            'k' => HistoryPrev(count),
+            'j' => HistoryNext(count),
+            'i' => StartInsert(Cursor),
+            'a' => StartInsert(After),
+            'I' => StartInsert(Start),
+            'A' => StartInsert(End),
+            _ => return Invalid
+        })
+    }
+}
+
+// whitespace, word characters and punctuation each make up words
+fn class(ch:char) -> u8 {
+    if ch.is_whitespace() {
+        0
+    } else if ch.is_alphanumeric() || ch == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+pub fn next_word_start(text:&Vec<char>, pos:usize) -> usize {
+    let mut i = pos;
+    if i < text.len() && class(text[i]) != 0 {
+        let c = class(text[i]);
+        while i < text.len() && class(text[i]) == c {
+            i += 1;
+        }
+    }
+    while i < text.len() && class(text[i]) == 0 {
+        i += 1;
+    }
+    return i;
+}
+
+pub fn prev_word_start(text:&Vec<char>, pos:usize) -> usize {
+    let mut i = pos;
+    while i > 0 && class(text[i - 1]) == 0 {
+        i -= 1;
+    }
+    if i > 0 {
+        let c = class(text[i - 1]);
+        while i > 0 && class(text[i - 1]) == c {
+            i -= 1;
+        }
+    }
+    return i;
+}
+
+// the last character of this word, or the next one if at its end
+pub fn word_end(text:&Vec<char>, pos:usize) -> usize {
+    if text.is_empty() {
+        return 0;
+    }
+    let mut i = pos + 1;
+    while i < text.len() && class(text[i]) == 0 {
+        i += 1;
+    }
+    if i >= text.len() {
+        return text.len() - 1;
+    }
+    let c = class(text[i]);
+    while i + 1 < text.len() && class(text[i + 1]) == c {
+        i += 1;
+    }
+    return i;
+}
+
+// where the cursor ends up after a motion
+pub fn motion_target(text:&Vec<char>, pos:usize, motion:ViMotion, count:usize) -> usize {
+    match motion {
+        LineStart | WholeLine => return 0,
+        LineEnd => return text.len(),
+        _ => {}
+    }
+    let mut to = pos;
+    for _ in 0..count {
+        to = match motion {
+            Left => if to > 0 {to - 1} else {0},
+            Right => min(to + 1, text.len()),
+            WordStart => next_word_start(text, to),
+            WordBack => prev_word_start(text, to),
+            WordEnd => word_end(text, to),
+            _ => to
+        };
+    }
+    return to;
+}
+
+// the characters an operator with a motion acts on
+pub fn motion_range(text:&Vec<char>, pos:usize, motion:ViMotion, count:usize) -> (usize, usize) {
+    if motion == WholeLine {
+        return (0, text.len());
+    }
+    let to = motion_target(text, pos, motion, count);
+    if motion == WordEnd {
+        // e takes in the character it ends on
+        return (pos, min(max(to, pos) + 1, text.len()));
+    } else if to < pos {
+        return (to, pos);
+    } else {
+        return (pos, to);
+    }
+}
+
+#[cfg(test)]
+fn push_all(parser:&mut ViParser, keys:&str) -> ViInput {
+    let mut out = Invalid;
+    for ch in keys.chars() {
+        out = parser.push(ch);
+    }
+    return out;
+}
+
+#[test]
+fn vi_parser_test() {
+    let mut parser = ViParser::new();
+    assert_eq!(push_all(&mut parser, "3w"), Command(Move(WordStart, 3)));
+    assert_eq!(push_all(&mut parser, "0"), Command(Move(LineStart, 1)));
+    assert_eq!(push_all(&mut parser, "2d3w"), Command(Operate(Delete, WordStart, 6)));
+    assert_eq!(push_all(&mut parser, "cw"), Command(Operate(Change, WordEnd, 1)));
+    assert_eq!(push_all(&mut parser, "yy"), Command(Operate(Yank, WholeLine, 1)));
+    assert_eq!(push_all(&mut parser, "d"), Pending);
+    assert_eq!(push_all(&mut parser, "z"), Invalid);
+    assert_eq!(push_all(&mut parser, "10x"), Command(DeleteChar(10)));
+}
+
+#[test]
+fn vi_motion_test() {
+    let text:Vec<char> = "echo foo.bar  baz".chars().collect();
+    assert_eq!(next_word_start(&text, 0), 5);
+    assert_eq!(next_word_start(&text, 5), 8);
+    assert_eq!(motion_target(&text, 0, WordStart, 4), 14);
+    assert_eq!(prev_word_start(&text, 14), 9);
+    assert_eq!(word_end(&text, 0), 3);
+    assert_eq!(word_end(&text, 3), 7);
+    assert_eq!(motion_range(&text, 5, WordEnd, 1), (5, 8));
+    assert_eq!(motion_range(&text, 8, WordBack, 1), (5, 8));
+}